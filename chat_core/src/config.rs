@@ -0,0 +1,219 @@
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::{
+    env,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+const OVERRIDE_SEPARATOR: &str = "__";
+
+/// Where a service finds its config file and which env vars override its fields
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigSource {
+    /// env var with the path of the config file, e.g. CHAT_CONFIG
+    pub path_env: &'static str,
+    /// tried in order when neither --config nor `path_env` is given
+    pub default_paths: &'static [&'static str],
+    /// fields are overridden by vars like <prefix>SERVER__PORT, e.g. CHAT_SERVER__PORT
+    pub override_prefix: &'static str,
+}
+
+/// Override vars that don't name a field of the config, e.g. a misspelled section
+#[derive(Debug, thiserror::Error)]
+#[error("unknown config overrides: {}", .0.join(", "))]
+pub struct UnknownOverrides(pub Vec<String>);
+
+impl ConfigSource {
+    /// Read the first config found of: `path` (the --config flag), `$path_env` and the default
+    /// paths, then apply the env overrides. Returns the config with the path it was read from.
+    pub fn load<T>(&self, path: Option<PathBuf>) -> Result<(T, PathBuf)>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.load_with_vars(path, env::vars())
+    }
+
+    /// [`load`](Self::load) with the given vars instead of the environment
+    pub fn load_with_vars<T>(
+        &self,
+        path: Option<PathBuf>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(T, PathBuf)>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let path = self.locate(path)?;
+        let reader = File::open(&path).with_context(|| format!("open {}", path.display()))?;
+        let mut value: Value = serde_yaml::from_reader(reader)
+            .with_context(|| format!("parse {} failed", path.display()))?;
+        // fields left to their defaults are only known after a round trip through the config
+        let fields = serde_yaml::from_value::<T>(value.clone())
+            .ok()
+            .and_then(|config| serde_yaml::to_value(config).ok())
+            .unwrap_or_else(|| value.clone());
+        self.apply_overrides(&mut value, &fields, vars)
+            .with_context(|| format!("invalid config {}", path.display()))?;
+        let config = serde_yaml::from_value(value)
+            .with_context(|| format!("invalid config {}", path.display()))?;
+        Ok((config, path))
+    }
+
+    // an explicit path must exist, the default locations are only tried
+    fn locate(&self, path: Option<PathBuf>) -> Result<PathBuf> {
+        if let Some(path) = path {
+            if !path.exists() {
+                bail!("config {} given by --config not found", path.display());
+            }
+            return Ok(path);
+        }
+        if let Ok(path) = env::var(self.path_env) {
+            let path = PathBuf::from(path);
+            if !path.exists() {
+                bail!(
+                    "config {} given by ${} not found",
+                    path.display(),
+                    self.path_env
+                );
+            }
+            return Ok(path);
+        }
+        self.default_paths
+            .iter()
+            .map(Path::new)
+            .find(|path| path.exists())
+            .map(Path::to_path_buf)
+            .context("Config file not found")
+    }
+
+    /// Set the fields named by <prefix><FIELD>__<FIELD> vars, e.g. CHAT_SERVER__PORT=7788.
+    /// Every var has to name a field of `fields`, the config with its defaults filled in.
+    /// Values are parsed as yaml scalars, except where the field is a string.
+    fn apply_overrides(
+        &self,
+        value: &mut Value,
+        fields: &Value,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(), UnknownOverrides> {
+        let mut unknown = Vec::new();
+        for (key, raw) in vars {
+            let Some(path) = key.strip_prefix(self.override_prefix) else {
+                continue;
+            };
+            // CHAT_CONFIG and other vars without a separator aren't overrides
+            if !path.contains(OVERRIDE_SEPARATOR) {
+                continue;
+            }
+            let segments: Vec<_> = path
+                .split(OVERRIDE_SEPARATOR)
+                .map(str::to_lowercase)
+                .collect();
+            let Some(field) = lookup(fields, &segments) else {
+                unknown.push(key);
+                continue;
+            };
+
+            let mut current = &mut *value;
+            for segment in segments {
+                if !current.is_mapping() {
+                    *current = Value::Mapping(Mapping::new());
+                }
+                let map = current.as_mapping_mut().expect("value should be a mapping");
+                current = map.entry(Value::String(segment)).or_insert(Value::Null);
+            }
+            *current = if field.is_some_and(Value::is_string) || current.is_string() {
+                Value::String(raw)
+            } else {
+                serde_yaml::from_str(&raw).unwrap_or(Value::String(raw))
+            };
+        }
+        if !unknown.is_empty() {
+            unknown.sort();
+            return Err(UnknownOverrides(unknown));
+        }
+        Ok(())
+    }
+}
+
+// Some(None) when the path goes below an unset optional section, whose fields aren't known
+fn lookup<'a>(fields: &'a Value, segments: &[String]) -> Option<Option<&'a Value>> {
+    let mut current = fields;
+    for segment in segments {
+        if current.is_null() {
+            return Some(None);
+        }
+        current = current.as_mapping()?.get(segment.as_str())?;
+    }
+    Some(Some(current))
+}
+
+/// The path given by `--config <path>`, `--config=<path>` or `-c <path>`, it takes precedence
+/// over the env var and the default paths of a [`ConfigSource`]
+pub fn config_arg() -> Option<PathBuf> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" || arg == "-c" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: ConfigSource = ConfigSource {
+        path_env: "TEST_CONFIG",
+        default_paths: &[],
+        override_prefix: "TEST_",
+    };
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn overrides_should_only_set_known_fields() -> Result<()> {
+        let mut value: Value = serde_yaml::from_str("server:\n  port: 6688\n  name: '1'\n")?;
+        let fields: Value =
+            serde_yaml::from_str("server:\n  port: 6688\n  name: '1'\n  limit: 10\nfile: null\n")?;
+        SOURCE.apply_overrides(
+            &mut value,
+            &fields,
+            vars(&[
+                ("TEST_SERVER__PORT", "7788"),
+                ("TEST_SERVER__NAME", "2"),
+                ("TEST_SERVER__LIMIT", "20"),
+                ("TEST_FILE__DIR", "/var/log"),
+                ("TEST_CONFIG", "/tmp/other.yaml"),
+                ("HOME", "/root"),
+            ]),
+        )?;
+        let expected: Value = serde_yaml::from_str(
+            "server:\n  port: 7788\n  name: '2'\n  limit: 20\nfile:\n  dir: /var/log\n",
+        )?;
+        assert_eq!(value, expected);
+
+        let ret = SOURCE.apply_overrides(
+            &mut value,
+            &fields,
+            vars(&[
+                ("TEST_SERVR__PORT", "7788"),
+                ("TEST_SERVER__PORT__MAX", "1"),
+                ("TEST_SERVER__PORT", "7789"),
+            ]),
+        );
+        let Err(UnknownOverrides(unknown)) = ret else {
+            panic!("expect unknown overrides");
+        };
+        assert_eq!(unknown, vec!["TEST_SERVER__PORT__MAX", "TEST_SERVR__PORT"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::{collections::BTreeMap, future::Future, time::Duration};
+use tokio::time::Instant;
+
+// a probe must answer even when the db hangs
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    Unavailable,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthOutput {
+    pub status: HealthStatus,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub checks: BTreeMap<String, CheckOutput>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckOutput {
+    pub status: HealthStatus,
+    pub latency_ms: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The process is up, nothing else is checked
+pub async fn health_handler() -> impl IntoResponse {
+    Json(HealthOutput {
+        status: HealthStatus::Ok,
+        checks: BTreeMap::new(),
+    })
+}
+
+/// Answer a readiness probe, 503 unless every check passed
+pub fn readiness(checks: BTreeMap<String, CheckOutput>) -> impl IntoResponse {
+    let ready = checks.values().all(|c| c.status == HealthStatus::Ok);
+    let (code, status) = if ready {
+        (StatusCode::OK, HealthStatus::Ok)
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, HealthStatus::Unavailable)
+    };
+    (code, Json(HealthOutput { status, checks }))
+}
+
+/// Time a check, it fails when it doesn't finish within 2 seconds
+pub async fn check(f: impl Future<Output = Result<(), String>>) -> CheckOutput {
+    let start = Instant::now();
+    let ret = tokio::time::timeout(CHECK_TIMEOUT, f)
+        .await
+        .unwrap_or_else(|_| Err(format!("timed out after {:?}", CHECK_TIMEOUT)));
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    match ret {
+        Ok(()) => CheckOutput {
+            status: HealthStatus::Ok,
+            latency_ms,
+            error: None,
+        },
+        Err(e) => CheckOutput {
+            status: HealthStatus::Unavailable,
+            latency_ms,
+            error: Some(e),
+        },
+    }
+}
+
+/// The db answers a query
+pub async fn db_check(pool: &PgPool) -> Result<(), String> {
+    sqlx::query("SELECT 1")
+        .execute(pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
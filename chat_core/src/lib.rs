@@ -0,0 +1,15 @@
+//! What chat_server and notify_server share for running in production: config loading, health
+//! checks, metrics, logging and tracing
+mod config;
+mod health;
+mod logging;
+mod metrics;
+mod telemetry;
+
+pub use self::metrics::{prometheus, render_metrics, track_metrics};
+pub use config::{config_arg, ConfigSource, UnknownOverrides};
+pub use health::{
+    check, db_check, health_handler, readiness, CheckOutput, HealthOutput, HealthStatus,
+};
+pub use logging::{init_logging, set_level, LogConfig, LogFileConfig, LogFormat, LogRotation};
+pub use telemetry::{shutdown_tracing, tracer_provider, TracingConfig};
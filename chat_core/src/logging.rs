@@ -0,0 +1,200 @@
+use crate::telemetry::{init_tracer, TracingConfig};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{env, io, path::PathBuf, sync::OnceLock};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
+
+// the subscriber is global, so is the handle to swap its filter
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// EnvFilter directives, e.g. "info" or "info,chat_server=debug,sqlx=warn", $RUST_LOG takes
+    /// precedence
+    pub level: String,
+    pub format: LogFormat,
+    /// write to rotated files instead of stdout
+    pub file: Option<LogFileConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// one object per line, for log collectors
+    Json,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogFileConfig {
+    pub dir: PathBuf,
+    /// files are named <prefix>.<date>, <server>.log when not set, e.g. chat_server.log.2024-05-01
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// older files are deleted, all are kept when not set
+    #[serde(default)]
+    pub max_files: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+impl LogConfig {
+    /// Add an error for every invalid field, named as under `log` in the config file
+    pub fn validate(&self, errors: &mut Vec<String>) {
+        let mut check = |ok: bool, field: &str, reason: &str| {
+            if !ok {
+                errors.push(format!("{}: {}", field, reason));
+            }
+        };
+        check(
+            EnvFilter::try_new(&self.level).is_ok(),
+            "log.level",
+            "must be EnvFilter directives like \"info,sqlx=warn\"",
+        );
+        if let Some(file) = &self.file {
+            check(
+                !file.dir.as_os_str().is_empty(),
+                "log.file.dir",
+                "must not be empty",
+            );
+            check(
+                file.prefix.as_ref().is_none_or(|p| !p.is_empty()),
+                "log.file.prefix",
+                "must not be empty",
+            );
+            check(
+                file.max_files != Some(0),
+                "log.file.max_files",
+                "must be positive",
+            );
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::Text,
+            file: None,
+        }
+    }
+}
+
+/// Install the global subscriber of `service`, its level can be changed later by `set_level`.
+/// Spans are also handed to OpenTelemetry, see `tracing.otlp_endpoint`.
+/// Lines are written by a background thread, keep the guard until exit so none get lost.
+pub fn init_logging(
+    service: &str,
+    config: &LogConfig,
+    tracing: &TracingConfig,
+) -> Result<WorkerGuard> {
+    let filter = match env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(directives) => EnvFilter::try_new(directives).context("invalid $RUST_LOG")?,
+        Err(_) => EnvFilter::try_new(&config.level).context("invalid log level")?,
+    };
+    let (filter, handle) = reload::Layer::new(filter);
+    let (writer, guard) = match &config.file {
+        Some(file) => tracing_appender::non_blocking(file_appender(service, file)?),
+        None => tracing_appender::non_blocking(io::stdout()),
+    };
+    let fmt = match config.format {
+        LogFormat::Text => fmt::layer()
+            .with_ansi(config.file.is_none())
+            .with_writer(writer)
+            .boxed(),
+        LogFormat::Json => fmt::layer().json().with_writer(writer).boxed(),
+    };
+    let tracer = init_tracer(service, tracing)?;
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt)
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()?;
+    FILTER.set(handle).ok();
+    Ok(guard)
+}
+
+/// Swap the level directives, a no-op when `init_logging` wasn't called, e.g. in tests, or while
+/// $RUST_LOG is set
+pub fn set_level(level: &str) -> Result<()> {
+    let Some(handle) = FILTER.get() else {
+        return Ok(());
+    };
+    if env::var_os(EnvFilter::DEFAULT_ENV).is_some() {
+        return Ok(());
+    }
+    let filter = EnvFilter::try_new(level).context("invalid log level")?;
+    handle.reload(filter)?;
+    Ok(())
+}
+
+fn file_appender(service: &str, file: &LogFileConfig) -> Result<RollingFileAppender> {
+    let rotation = match file.rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    let prefix = match &file.prefix {
+        Some(prefix) => prefix.clone(),
+        None => format!("{}.log", service),
+    };
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(prefix);
+    if let Some(n) = file.max_files {
+        builder = builder.max_log_files(n);
+    }
+    builder
+        .build(&file.dir)
+        .with_context(|| format!("open log dir {}", file.dir.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_should_report_invalid_fields() {
+        let mut errors = Vec::new();
+        LogConfig::default().validate(&mut errors);
+        assert!(errors.is_empty());
+
+        let config = LogConfig {
+            level: "info,sqlx=loud".to_string(),
+            format: LogFormat::Json,
+            file: Some(LogFileConfig {
+                dir: PathBuf::from("/var/log/chat"),
+                prefix: Some(String::new()),
+                rotation: LogRotation::Daily,
+                max_files: Some(0),
+            }),
+        };
+        config.validate(&mut errors);
+        let fields: Vec<_> = errors
+            .iter()
+            .filter_map(|e| e.split_once(':').map(|(field, _)| field))
+            .collect();
+        assert_eq!(
+            fields,
+            vec!["log.level", "log.file.prefix", "log.file.max_files"]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    http::header::CONTENT_TYPE,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics::{counter, histogram};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use std::{sync::OnceLock, time::Instant};
+
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+const REQUESTS_TOTAL: &str = "http_requests_total";
+const REQUEST_DURATION: &str = "http_request_duration_seconds";
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The prometheus recorder, installed globally on first use. All histograms are latencies and
+/// share the buckets
+pub fn prometheus() -> &'static PrometheusHandle {
+    static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+    HANDLE.get_or_init(|| {
+        PrometheusBuilder::new()
+            .set_buckets(LATENCY_BUCKETS)
+            .expect("latency buckets should not be empty")
+            .install_recorder()
+            .expect("install metrics recorder failed")
+    })
+}
+
+/// Everything recorded so far in the prometheus text format
+pub fn render_metrics() -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        prometheus().render(),
+    )
+}
+
+/// Count requests and time them by method, route and status
+pub async fn track_metrics(req: Request, next: Next) -> Response {
+    let start = Instant::now();
+    // the route template, not the path, so ids don't blow up the number of series
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = req.method().to_string();
+
+    let res = next.run(req).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", res.status().as_u16().to_string()),
+    ];
+    counter!(REQUESTS_TOTAL, &labels).increment(1);
+    histogram!(REQUEST_DURATION, &labels).record(start.elapsed().as_secs_f64());
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use axum::{body::Body, middleware::from_fn, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn track_metrics_should_label_by_route() -> Result<()> {
+        let handle = prometheus();
+        let app = Router::new()
+            .nest(
+                "/metrics-test",
+                Router::new().route("/chat/:id", get(|| async { "ok" })),
+            )
+            .layer(from_fn(track_metrics));
+        for id in [1, 2] {
+            let req = Request::get(format!("/metrics-test/chat/{}", id)).body(Body::empty())?;
+            app.clone().oneshot(req).await?;
+        }
+
+        let output = handle.render();
+        let line = output
+            .lines()
+            .find(|l| l.starts_with(REQUESTS_TOTAL) && l.contains("/metrics-test/chat/:id"))
+            .expect("request counter");
+        assert!(line.contains(r#"method="GET""#));
+        assert!(line.contains(r#"status="200""#));
+        assert!(line.ends_with(" 2"));
+        assert!(output.contains(&format!("{}_bucket", REQUEST_DURATION)));
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::Result;
+use opentelemetry::{global, trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{Sampler, Tracer, TracerProvider},
+    Resource,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use tracing::warn;
+
+// kept to flush the spans still batched on exit
+static PROVIDER: OnceLock<TracerProvider> = OnceLock::new();
+
+/// OpenTelemetry traces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+    /// OTLP/HTTP traces url, e.g. http://localhost:4318/v1/traces, spans aren't exported without it
+    pub otlp_endpoint: Option<String>,
+    /// the name of the server when not set, e.g. chat_server
+    pub service_name: Option<String>,
+    /// share of new traces sampled, work carrying a traceparent follows its caller
+    pub sample_ratio: f64,
+}
+
+impl TracingConfig {
+    /// Add an error for every invalid field, named as under `tracing` in the config file
+    pub fn validate(&self, errors: &mut Vec<String>) {
+        let endpoint_ok = self
+            .otlp_endpoint
+            .as_ref()
+            .is_none_or(|url| url.starts_with("http://") || url.starts_with("https://"));
+        if !endpoint_ok {
+            errors.push("tracing.otlp_endpoint: must be an http(s) url".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.sample_ratio) {
+            errors.push("tracing.sample_ratio: must be between 0 and 1".to_string());
+        }
+    }
+
+    fn service_name<'a>(&'a self, service: &'a str) -> &'a str {
+        self.service_name.as_deref().unwrap_or(service)
+    }
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: None,
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+/// The tracer behind the OpenTelemetry layer, installed globally with the W3C propagator.
+/// Spans get trace ids either way, they are only exported when `tracing.otlp_endpoint` is set.
+pub(crate) fn init_tracer(service: &str, config: &TracingConfig) -> Result<Tracer> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let provider = tracer_provider(service, config)?;
+    let tracer = provider.tracer(config.service_name(service).to_string());
+    global::set_tracer_provider(provider.clone());
+    PROVIDER.set(provider).ok();
+    Ok(tracer)
+}
+
+/// A provider sampling and exporting as configured, not installed anywhere
+pub fn tracer_provider(service: &str, config: &TracingConfig) -> Result<TracerProvider> {
+    let mut builder = TracerProvider::builder()
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio,
+        ))))
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            config.service_name(service).to_string(),
+        )]));
+    if let Some(endpoint) = &config.otlp_endpoint {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()?;
+        builder = builder.with_batch_exporter(exporter, runtime::Tokio);
+    }
+    Ok(builder.build())
+}
+
+/// Export the spans not sent yet, called before the process exits
+pub fn shutdown_tracing() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.shutdown() {
+            warn!("flush spans failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_should_report_invalid_fields() {
+        let mut errors = Vec::new();
+        TracingConfig::default().validate(&mut errors);
+        assert!(errors.is_empty());
+
+        let config = TracingConfig {
+            otlp_endpoint: Some("localhost:4318".to_string()),
+            service_name: None,
+            sample_ratio: 1.5,
+        };
+        config.validate(&mut errors);
+        assert_eq!(
+            errors,
+            vec![
+                "tracing.otlp_endpoint: must be an http(s) url",
+                "tracing.sample_ratio: must be between 0 and 1",
+            ]
+        );
+    }
+}
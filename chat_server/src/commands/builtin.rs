@@ -0,0 +1,319 @@
+use super::{Command, CommandContext, CommandOutput};
+use crate::{
+    models::{Chat, ChatType, CreateMessage, Message, MessageKind, UpdateChat},
+    validation::Validate,
+    AppError, User,
+};
+use async_trait::async_trait;
+use sqlx::PgConnection;
+
+pub(super) fn all() -> Vec<Box<dyn Command>> {
+    vec![
+        Box::new(MeCommand),
+        Box::new(TopicCommand),
+        Box::new(InviteCommand),
+        Box::new(LeaveCommand),
+        Box::new(MuteCommand),
+    ]
+}
+
+struct MeCommand;
+struct TopicCommand;
+struct InviteCommand;
+struct LeaveCommand;
+struct MuteCommand;
+
+#[async_trait]
+impl Command for MeCommand {
+    fn name(&self) -> &'static str {
+        "me"
+    }
+
+    fn usage(&self) -> &'static str {
+        "/me <action>"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &CommandContext<'_>,
+        args: &str,
+    ) -> Result<CommandOutput, AppError> {
+        if args.is_empty() {
+            return Err(usage_error(self));
+        }
+        post(ctx, args.to_string(), MessageKind::Action).await
+    }
+}
+
+#[async_trait]
+impl Command for TopicCommand {
+    fn name(&self) -> &'static str {
+        "topic"
+    }
+
+    fn usage(&self) -> &'static str {
+        "/topic [new topic], without a topic it is cleared"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &CommandContext<'_>,
+        args: &str,
+    ) -> Result<CommandOutput, AppError> {
+        let topic = Some(args).filter(|t| !t.is_empty());
+        UpdateChat {
+            topic: topic.map(str::to_string),
+            ..Default::default()
+        }
+        .check()?;
+        let state = ctx.state;
+        let base_dir = &state.config.server.base_dir;
+        let mut tx = state.pool.begin().await?;
+        let message = Chat::change_topic(ctx.chat.id, ctx.user, topic, base_dir, &mut tx).await?;
+        tx.commit().await?;
+        Ok(CommandOutput::Message(message))
+    }
+}
+
+#[async_trait]
+impl Command for InviteCommand {
+    fn name(&self) -> &'static str {
+        "invite"
+    }
+
+    fn usage(&self) -> &'static str {
+        "/invite @<email or user id>"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &CommandContext<'_>,
+        args: &str,
+    ) -> Result<CommandOutput, AppError> {
+        if ctx.chat.r#type == ChatType::Single {
+            return Err(AppError::InvalidInput(
+                "can't invite anyone to a direct message".to_string(),
+            ));
+        }
+        let reference = args.strip_prefix('@').ok_or_else(|| usage_error(self))?;
+        let invitee = resolve_user(reference, &ctx.state.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("user {}", args)))?;
+        let mut tx = ctx.state.pool.begin().await?;
+        if !Chat::add_member(ctx.chat.id, invitee.id, &mut *tx).await? {
+            return Ok(CommandOutput::Ephemeral(format!(
+                "{} is already a member",
+                invitee.fullname
+            )));
+        }
+        let content = format!("{} invited {}", ctx.user.fullname, invitee.fullname);
+        let message = insert_system(ctx, content, &mut tx).await?;
+        tx.commit().await?;
+        Ok(CommandOutput::Message(message))
+    }
+}
+
+#[async_trait]
+impl Command for LeaveCommand {
+    fn name(&self) -> &'static str {
+        "leave"
+    }
+
+    fn usage(&self) -> &'static str {
+        "/leave"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &CommandContext<'_>,
+        _args: &str,
+    ) -> Result<CommandOutput, AppError> {
+        if ctx.chat.r#type == ChatType::Single {
+            return Err(AppError::InvalidInput(
+                "can't leave a direct message".to_string(),
+            ));
+        }
+        let mut tx = ctx.state.pool.begin().await?;
+        // post while still a member
+        let content = format!("{} left", ctx.user.fullname);
+        let message = insert_system(ctx, content, &mut tx).await?;
+        Chat::remove_member(ctx.chat.id, ctx.user.id, &mut *tx).await?;
+        tx.commit().await?;
+        Ok(CommandOutput::Message(message))
+    }
+}
+
+#[async_trait]
+impl Command for MuteCommand {
+    fn name(&self) -> &'static str {
+        "mute"
+    }
+
+    fn usage(&self) -> &'static str {
+        "/mute [off]"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &CommandContext<'_>,
+        args: &str,
+    ) -> Result<CommandOutput, AppError> {
+        let muted = match args {
+            "" | "on" => true,
+            "off" => false,
+            _ => return Err(usage_error(self)),
+        };
+        Chat::set_muted(ctx.chat.id, ctx.user.id, muted, &ctx.state.pool).await?;
+        let text = if muted {
+            "Notifications for this chat are muted"
+        } else {
+            "Notifications for this chat are back on"
+        };
+        Ok(CommandOutput::Ephemeral(text.to_string()))
+    }
+}
+
+async fn post(
+    ctx: &CommandContext<'_>,
+    content: String,
+    kind: MessageKind,
+) -> Result<CommandOutput, AppError> {
+    let input = CreateMessage {
+        content,
+        kind,
+        ..Default::default()
+    };
+    let state = ctx.state;
+    let base_dir = &state.config.server.base_dir;
+    let message = Message::create(&input, ctx.chat.id, ctx.user.id, base_dir, &state.pool).await?;
+    Ok(CommandOutput::Message(message))
+}
+
+// a system message in the transaction that changes the membership, so both or neither happen
+async fn insert_system(
+    ctx: &CommandContext<'_>,
+    content: String,
+    conn: &mut PgConnection,
+) -> Result<Message, AppError> {
+    let input = CreateMessage {
+        content,
+        kind: MessageKind::System,
+        ..Default::default()
+    };
+    let base_dir = &ctx.state.config.server.base_dir;
+    Message::insert(&input, ctx.chat.id, ctx.user.id, base_dir, conn).await
+}
+
+// users are referred to by email or id: @alice@acme.org, @42
+async fn resolve_user(reference: &str, pool: &sqlx::PgPool) -> Result<Option<User>, AppError> {
+    match reference.parse::<i64>() {
+        Ok(id) => User::find_by_id(id, pool).await,
+        Err(_) => User::find_by_email(reference, pool).await,
+    }
+}
+
+fn usage_error(command: &dyn Command) -> AppError {
+    AppError::InvalidInput(format!("usage: {}", command.usage()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        commands::{CommandOutput, CommandRegistry},
+        models::{Chat, CreateMessage, CreateUser, MessageKind},
+        AppConfig, AppError, AppState, User,
+    };
+    use anyhow::Result;
+
+    async fn setup() -> Result<(sqlx_db_tester::TestPg, AppState, User, User)> {
+        let config = AppConfig::load()?;
+        let (tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        let input = CreateUser::new("Alice Chen", "Alice@Acme.org", "Hunter42");
+        let other = User::create(&input, &state.pool).await?;
+        sqlx::query("INSERT INTO chats (name, type, members) VALUES ('general', 'group', $1)")
+            .bind(vec![user.id])
+            .execute(&state.pool)
+            .await?;
+        Ok((tdb, state, user, other))
+    }
+
+    async fn run(
+        state: &AppState,
+        user: &User,
+        content: &str,
+    ) -> Result<Option<CommandOutput>, AppError> {
+        let registry = CommandRegistry::new(&state.config.outbound);
+        let mut input = CreateMessage::new(content);
+        registry.dispatch(state, user, 1, &mut input).await
+    }
+
+    #[tokio::test]
+    async fn builtin_commands_should_work() -> Result<()> {
+        let (_tdb, state, user, other) = setup().await?;
+
+        let Some(CommandOutput::Message(message)) = run(&state, &user, "/me waves").await? else {
+            panic!("expect a message");
+        };
+        assert_eq!(message.kind, MessageKind::Action);
+        assert_eq!(message.content, "waves");
+
+        run(&state, &user, "/topic release day").await?;
+        let chat = Chat::find_by_id(1, &state.pool).await?.expect("chat");
+        assert_eq!(chat.topic.as_deref(), Some("release day"));
+
+        let Some(CommandOutput::Message(message)) =
+            run(&state, &user, "/invite @alice@acme.org").await?
+        else {
+            panic!("expect a message");
+        };
+        assert_eq!(message.kind, MessageKind::System);
+        assert_eq!(message.content, "Tyr Chen invited Alice Chen");
+        let output = run(&state, &user, &format!("/invite @{}", other.id)).await?;
+        assert!(matches!(output, Some(CommandOutput::Ephemeral(_))));
+
+        let output = run(&state, &other, "/mute").await?;
+        assert!(matches!(output, Some(CommandOutput::Ephemeral(_))));
+        let muted: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM chat_mutes WHERE chat_id = 1 AND user_id = $1)",
+        )
+        .bind(other.id)
+        .fetch_one(&state.pool)
+        .await?;
+        assert!(muted);
+
+        run(&state, &other, "/leave").await?;
+        let chat = Chat::find_by_id(1, &state.pool).await?.expect("chat");
+        assert_eq!(chat.members, vec![user.id]);
+
+        sqlx::query("INSERT INTO chats (name, type, members) VALUES ('', 'single', $1)")
+            .bind(vec![user.id, other.id])
+            .execute(&state.pool)
+            .await?;
+        let mut input = CreateMessage::new("/leave");
+        let registry = CommandRegistry::new(&state.config.outbound);
+        let ret = registry.dispatch(&state, &user, 2, &mut input).await;
+        assert!(matches!(ret, Err(AppError::InvalidInput(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn plain_and_unknown_commands_should_be_handled() -> Result<()> {
+        let (_tdb, state, user, _) = setup().await?;
+        let registry = CommandRegistry::new(&state.config.outbound);
+
+        let mut input = CreateMessage::new("//me is not a command");
+        assert!(registry
+            .dispatch(&state, &user, 1, &mut input)
+            .await?
+            .is_none());
+        assert_eq!(input.content, "/me is not a command");
+
+        let ret = run(&state, &user, "/nope").await;
+        assert!(matches!(ret, Err(AppError::InvalidInput(_))));
+        let ret = run(&state, &user, "/invite alice").await;
+        assert!(matches!(ret, Err(AppError::InvalidInput(_))));
+        Ok(())
+    }
+}
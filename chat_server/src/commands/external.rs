@@ -0,0 +1,212 @@
+use super::{CommandContext, CommandOutput};
+use crate::{
+    models::{CreateMessage, Message, SlashCommand},
+    telemetry::inject_context,
+    utils::{check_url, sign_payload},
+    AppError,
+};
+use chrono::Utc;
+use reqwest::{
+    header::{HeaderMap, CONTENT_TYPE},
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+const COMMAND_TIMESTAMP_HEADER: &str = "x-command-timestamp";
+const COMMAND_SIGNATURE_HEADER: &str = "x-command-signature";
+
+/// Body POSTed to an external command
+#[derive(Debug, Serialize, Deserialize)]
+struct CommandRequest<'a> {
+    command: String,
+    text: &'a str,
+    chat_id: i64,
+    user_id: i64,
+    user_name: &'a str,
+}
+
+/// What an external command answers with
+#[derive(Debug, Serialize, Deserialize)]
+struct CommandResponse {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    response_type: ResponseType,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ResponseType {
+    /// only the sender sees the response
+    #[default]
+    Ephemeral,
+    /// the response is posted to the chat by the command's bot
+    InChannel,
+}
+
+pub(super) async fn execute(
+    client: &Client,
+    command: &SlashCommand,
+    ctx: &CommandContext<'_>,
+    args: &str,
+) -> Result<CommandOutput, AppError> {
+    let request = CommandRequest {
+        command: format!("/{}", command.name),
+        text: args,
+        chat_id: ctx.chat.id,
+        user_id: ctx.user.id,
+        user_name: &ctx.user.fullname,
+    };
+    if let Err(e) = check_url(&command.url, &ctx.state.config.outbound).await {
+        warn!(
+            "command /{} of chat {} blocked: {}",
+            command.name, command.chat_id, e
+        );
+        return Ok(CommandOutput::Ephemeral(format!(
+            "/{} can't be reached",
+            command.name
+        )));
+    }
+    let body = serde_json::to_vec(&request).expect("command request should serialize");
+    let timestamp = Utc::now().timestamp();
+    // the command's own spans can join the trace of the message
+    let mut trace_headers = HeaderMap::new();
+    inject_context(&mut trace_headers);
+    let ret = client
+        .post(&command.url)
+        .headers(trace_headers)
+        .header(CONTENT_TYPE, "application/json")
+        .header(COMMAND_TIMESTAMP_HEADER, timestamp)
+        .header(
+            COMMAND_SIGNATURE_HEADER,
+            sign_payload(&command.secret, timestamp, &body),
+        )
+        .body(body)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status());
+    let res = match ret {
+        Ok(res) => res.json::<CommandResponse>().await,
+        Err(e) => Err(e),
+    };
+    // failures are the command's problem, tell the sender instead of failing the request
+    let response = match res {
+        Ok(response) => response,
+        Err(e) => {
+            warn!(
+                "command /{} of chat {} failed: {}",
+                command.name, command.chat_id, e
+            );
+            return Ok(CommandOutput::Ephemeral(format!(
+                "/{} didn't respond, try again later",
+                command.name
+            )));
+        }
+    };
+
+    match response.response_type {
+        ResponseType::Ephemeral => Ok(CommandOutput::Ephemeral(response.text)),
+        ResponseType::InChannel => {
+            let state = ctx.state;
+            let input = CreateMessage {
+                content: response.text,
+                ..Default::default()
+            };
+            let base_dir = &state.config.server.base_dir;
+            let message = Message::create(
+                &input,
+                command.chat_id,
+                command.bot_id,
+                base_dir,
+                &state.pool,
+            )
+            .await?;
+            Ok(CommandOutput::Message(message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::CommandRegistry,
+        models::{CreateSlashCommand, CreateUser},
+        AppConfig, AppState, User,
+    };
+    use anyhow::Result;
+    use axum::{routing::post, Json, Router};
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+
+    // echoes the text back, in channel when asked to
+    async fn echo(Json(req): Json<Value>) -> Json<Value> {
+        let text = req["text"].as_str().unwrap_or_default();
+        let response_type = if text.starts_with("public") {
+            "in_channel"
+        } else {
+            "ephemeral"
+        };
+        Json(json!({ "text": format!("echo: {}", text), "response_type": response_type }))
+    }
+
+    #[tokio::test]
+    async fn external_command_should_respond() -> Result<()> {
+        let mut config = AppConfig::load()?;
+        config.outbound.allowed_hosts = vec!["127.0.0.1".to_string()];
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/echo", listener.local_addr()?);
+        let app = Router::new().route("/echo", post(echo));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        sqlx::query("INSERT INTO chats (name, type, members) VALUES ('general', 'group', $1)")
+            .bind(vec![user.id])
+            .execute(&state.pool)
+            .await?;
+        let input = CreateSlashCommand {
+            name: "echo".to_string(),
+            url,
+            description: None,
+        };
+        let registry = CommandRegistry::new(&state.config.outbound);
+        let reserved = registry.builtin_names();
+        let outbound = &state.config.outbound;
+        let command =
+            SlashCommand::create(1, &user, &input, &reserved, outbound, &state.pool).await?;
+
+        let mut input = CreateMessage::new("/echo hi");
+        let output = registry.dispatch(&state, &user, 1, &mut input).await?;
+        assert!(matches!(output, Some(CommandOutput::Ephemeral(text)) if text == "echo: hi"));
+
+        let mut input = CreateMessage::new("/echo public hi");
+        let Some(CommandOutput::Message(message)) =
+            registry.dispatch(&state, &user, 1, &mut input).await?
+        else {
+            panic!("expect a message");
+        };
+        assert_eq!(message.sender_id, command.bot_id);
+        assert_eq!(message.content, "echo: public hi");
+
+        // built-in names are reserved
+        let input = CreateSlashCommand {
+            name: "me".to_string(),
+            url: command.url,
+            description: None,
+        };
+        let ret = SlashCommand::create(1, &user, &input, &reserved, outbound, &state.pool).await;
+        assert!(matches!(ret, Err(AppError::InvalidInput(_))));
+
+        let input = CreateSlashCommand {
+            name: "meta".to_string(),
+            url: "http://169.254.169.254/latest/meta-data".to_string(),
+            description: None,
+        };
+        let ret = SlashCommand::create(1, &user, &input, &reserved, outbound, &state.pool).await;
+        assert!(matches!(ret, Err(AppError::InvalidInput(_))));
+        Ok(())
+    }
+}
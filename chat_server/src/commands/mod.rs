@@ -0,0 +1,164 @@
+mod builtin;
+mod external;
+
+use crate::{
+    config::OutboundConfig,
+    models::{Chat, CreateMessage, Message, SlashCommand},
+    utils::outbound_client,
+    AppError, AppState, User,
+};
+use async_trait::async_trait;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+use utoipa::ToSchema;
+
+// external commands should answer quickly, the sender is waiting
+const EXTERNAL_COMMAND_TIMEOUT_SECS: u64 = 5;
+
+/// What the sender of a message gets back
+#[derive(Debug)]
+pub(crate) enum CommandOutput {
+    /// a message posted to the chat
+    Message(Message),
+    /// a reply only the sender sees, it is not stored
+    Ephemeral(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub(crate) struct EphemeralOutput {
+    pub(crate) ephemeral: bool,
+    pub(crate) text: String,
+}
+
+pub(crate) struct CommandContext<'a> {
+    pub(crate) state: &'a AppState,
+    pub(crate) user: &'a User,
+    pub(crate) chat: Chat,
+}
+
+#[async_trait]
+pub(crate) trait Command: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    /// shown when the arguments are invalid
+    fn usage(&self) -> &'static str;
+
+    async fn execute(
+        &self,
+        ctx: &CommandContext<'_>,
+        args: &str,
+    ) -> Result<CommandOutput, AppError>;
+}
+
+/// Built-in commands run in process, anything else is looked up in the chat's external commands
+pub(crate) struct CommandRegistry {
+    builtins: HashMap<&'static str, Box<dyn Command>>,
+    client: Client,
+}
+
+impl CommandRegistry {
+    pub(crate) fn new(outbound: &OutboundConfig) -> Self {
+        let mut registry = Self {
+            builtins: HashMap::new(),
+            client: outbound_client(Duration::from_secs(EXTERNAL_COMMAND_TIMEOUT_SECS), outbound)
+                .expect("build command http client failed"),
+        };
+        for command in builtin::all() {
+            registry.register(command);
+        }
+        registry
+    }
+
+    pub(crate) fn register(&mut self, command: Box<dyn Command>) {
+        self.builtins.insert(command.name(), command);
+    }
+
+    pub(crate) fn builtin_names(&self) -> Vec<&'static str> {
+        self.builtins.keys().copied().collect()
+    }
+
+    /// Run the command in the message if there is one, returns None for plain messages.
+    /// A leading `//` escapes the slash and sends the rest as text.
+    pub(crate) async fn dispatch(
+        &self,
+        state: &AppState,
+        user: &User,
+        chat_id: i64,
+        input: &mut CreateMessage,
+    ) -> Result<Option<CommandOutput>, AppError> {
+        let content = input.content.trim_start();
+        if content.starts_with("//") {
+            input.content = content[1..].to_string();
+            return Ok(None);
+        }
+        let Some((name, args)) = parse_command(content) else {
+            return Ok(None);
+        };
+
+        let chat = Chat::find_for_member(chat_id, user.id, &state.pool).await?;
+        let ctx = CommandContext { state, user, chat };
+        if let Some(command) = self.builtins.get(name.as_str()) {
+            return command.execute(&ctx, args).await.map(Some);
+        }
+        match SlashCommand::find(chat_id, &name, &state.pool).await? {
+            Some(command) => external::execute(&self.client, &command, &ctx, args)
+                .await
+                .map(Some),
+            None => Err(AppError::InvalidInput(format!(
+                "unknown command: /{}",
+                name
+            ))),
+        }
+    }
+}
+
+impl IntoResponse for CommandOutput {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Message(message) => (StatusCode::CREATED, Json(message)).into_response(),
+            Self::Ephemeral(text) => {
+                let output = EphemeralOutput {
+                    ephemeral: true,
+                    text,
+                };
+                (StatusCode::OK, Json(output)).into_response()
+            }
+        }
+    }
+}
+
+// "/Topic  new topic" => ("topic", "new topic")
+fn parse_command(content: &str) -> Option<(String, &str)> {
+    let rest = content.strip_prefix('/')?;
+    let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if name.is_empty() {
+        return None;
+    }
+    Some((name.to_lowercase(), args.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_command_should_work() {
+        assert_eq!(
+            parse_command("/me waves"),
+            Some(("me".to_string(), "waves"))
+        );
+        assert_eq!(
+            parse_command("/TOPIC  release  day "),
+            Some(("topic".to_string(), "release  day"))
+        );
+        assert_eq!(parse_command("/leave"), Some(("leave".to_string(), "")));
+        assert_eq!(parse_command("/ not a command"), None);
+        assert_eq!(parse_command("hello /me"), None);
+    }
+}
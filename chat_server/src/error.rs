@@ -1,12 +1,40 @@
-use axum::http::StatusCode;
+use crate::{middlewares::RequestContext, validation::ValidationErrors};
+use axum::extract::rejection::JsonRejection;
+use axum::http::{header, HeaderValue, StatusCode};
 use axum::response::Json;
 use axum::response::{IntoResponse, Response};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use thiserror::Error;
+use tracing::error;
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize)]
+pub(crate) const PROBLEM_JSON: &str = "application/problem+json";
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ErrorOutput {
+    /// stable, machine-readable, e.g. "not_found"
+    pub code: String,
+    /// human-readable, may change
     pub error: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// RFC 7807 problem details, sent when the client accepts application/problem+json
+#[derive(Debug, Serialize)]
+struct ProblemOutput<'a> {
+    r#type: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: &'a str,
+    code: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<&'a Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<&'a str>,
 }
 
 #[derive(Error, Debug)]
@@ -14,6 +42,27 @@ pub enum AppError {
     #[error("email already exists: {0}")]
     EmailAlreadyExists(String),
 
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+
+    #[error("too many requests, retry after {0} seconds")]
+    TooManyRequests(u64),
+
+    #[error("payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("validation failed: {0}")]
+    ValidationError(ValidationErrors),
+
+    #[error("invalid json: {}", .0.body_text())]
+    JsonRejection(#[from] JsonRejection),
+
     #[error("sql error: {0}")]
     SqlxError(#[from] sqlx::Error),
 
@@ -25,26 +74,185 @@ pub enum AppError {
 
     #[error("http header parse error: {0}")]
     HttpHeaderError(#[from] axum::http::header::InvalidHeaderValue),
+
+    #[error("mail error: {0}")]
+    MailError(String),
+
+    #[error("totp error: {0}")]
+    TotpError(String),
+
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
 }
 
 impl ErrorOutput {
-    pub fn new(error: impl Into<String>) -> Self {
+    pub fn new(code: impl Into<String>, error: impl Into<String>) -> Self {
         Self {
+            code: code.into(),
             error: error.into(),
+            details: None,
+            request_id: RequestContext::current().and_then(|ctx| ctx.request_id),
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Render as JSON, or as problem details if the client asked for them
+    pub fn into_response_with(self, status: StatusCode) -> Response {
+        let problem_json = RequestContext::current().is_some_and(|ctx| ctx.problem_json);
+        if !problem_json {
+            return (status, Json(self)).into_response();
         }
+        let problem = ProblemOutput {
+            r#type: "about:blank",
+            title: status.canonical_reason().unwrap_or("Unknown Error"),
+            status: status.as_u16(),
+            detail: &self.error,
+            code: &self.code,
+            details: self.details.as_ref(),
+            request_id: self.request_id.as_deref(),
+        };
+        let mut res = (status, Json(problem)).into_response();
+        res.headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        res
     }
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response<axum::body::Body> {
-        let status = match &self {
+impl AppError {
+    /// Stable code clients can match on, the message may change
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::EmailAlreadyExists(_) => "email_already_exists",
+            Self::PermissionDenied(_) => "permission_denied",
+            Self::TooManyRequests(_) => "too_many_requests",
+            Self::PayloadTooLarge(_) => "payload_too_large",
+            Self::NotFound(_) => "not_found",
+            Self::InvalidInput(_) => "invalid_input",
+            Self::ValidationError(_) => "validation_failed",
+            Self::JsonRejection(_) => "invalid_json",
+            Self::PasswordHashError(_) => "invalid_password",
+            Self::JwtError(_) => "invalid_token",
+            Self::HttpHeaderError(_) => "invalid_header",
+            Self::SqlxError(_) | Self::MailError(_) | Self::TotpError(_) | Self::IoError(_) => {
+                "internal_error"
+            }
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
             Self::SqlxError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::PasswordHashError(_) => StatusCode::UNPROCESSABLE_ENTITY,
             Self::JwtError(_) => StatusCode::FORBIDDEN,
             Self::HttpHeaderError(_) => StatusCode::UNPROCESSABLE_ENTITY,
             Self::EmailAlreadyExists(_) => StatusCode::CONFLICT,
+            Self::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            Self::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::JsonRejection(e) => e.status(),
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::PermissionDenied(_) => StatusCode::FORBIDDEN,
+            Self::MailError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::TotpError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::IoError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response<axum::body::Body> {
+        let status = self.status();
+        // internal errors may carry db or file system details, log them and keep them from clients
+        let message = if status.is_server_error() {
+            let request_id = RequestContext::current().and_then(|ctx| ctx.request_id);
+            error!(request_id, "internal error: {}", self);
+            "internal server error".to_string()
+        } else {
+            self.to_string()
         };
+        let mut output = ErrorOutput::new(self.code(), message);
+        match &self {
+            Self::TooManyRequests(secs) => {
+                output = output.with_details(json!({ "retry_after": secs }));
+            }
+            Self::ValidationError(errors) => {
+                output = output.with_details(json!({ "fields": errors }));
+            }
+            _ => {}
+        }
+
+        let mut res = output.into_response_with(status);
+        if let Self::TooManyRequests(secs) = self {
+            res.headers_mut().insert(header::RETRY_AFTER, secs.into());
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middlewares::set_layer;
+    use anyhow::Result;
+    use axum::{body::Body, extract::Request, routing::get, Router};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let app = Router::new()
+            .route(
+                "/db",
+                get(|| async { Err::<(), _>(AppError::SqlxError(sqlx::Error::PoolTimedOut)) }),
+            )
+            .route(
+                "/busy",
+                get(|| async { Err::<(), _>(AppError::TooManyRequests(30)) }),
+            );
+        set_layer(app)
+    }
+
+    async fn call(accept: &str, uri: &str) -> Result<(StatusCode, Option<String>, Value)> {
+        let req = Request::get(uri)
+            .header("x-request-id", "req-42")
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())?;
+        let res = app().oneshot(req).await?;
+        let content_type = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let status = res.status();
+        let body = res.into_body().collect().await?.to_bytes();
+        Ok((status, content_type, serde_json::from_slice(&body)?))
+    }
+
+    #[tokio::test]
+    async fn internal_errors_should_be_sanitized() -> Result<()> {
+        let (status, _, body) = call("application/json", "/db").await?;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        let output: ErrorOutput = serde_json::from_value(body)?;
+        assert_eq!(output.code, "internal_error");
+        assert_eq!(output.error, "internal server error");
+        assert_eq!(output.request_id.as_deref(), Some("req-42"));
+        Ok(())
+    }
 
-        (status, Json(ErrorOutput::new(self.to_string()))).into_response()
+    #[tokio::test]
+    async fn errors_should_render_as_problem_details_on_request() -> Result<()> {
+        let (status, content_type, body) = call(PROBLEM_JSON, "/busy").await?;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(content_type.as_deref(), Some(PROBLEM_JSON));
+        assert_eq!(body["status"], 429);
+        assert_eq!(body["title"], "Too Many Requests");
+        assert_eq!(body["code"], "too_many_requests");
+        assert_eq!(body["details"]["retry_after"], 30);
+        assert_eq!(body["request_id"], "req-42");
+        Ok(())
     }
 }
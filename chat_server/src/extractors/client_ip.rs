@@ -0,0 +1,77 @@
+use crate::AppState;
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, Extensions, HeaderMap},
+};
+use std::{
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
+
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+/// The ip of the client, from x-forwarded-for when running behind a trusted proxy, otherwise
+/// from the peer address of the connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientIp(pub IpAddr);
+
+impl ClientIp {
+    pub fn extract(headers: &HeaderMap, extensions: &Extensions, trust_proxy: bool) -> Self {
+        let forwarded = trust_proxy
+            .then(|| headers.get(FORWARDED_FOR_HEADER))
+            .flatten()
+            .and_then(|v| v.to_str().ok())
+            // the left-most entry is the original client
+            .and_then(|v| v.split(',').next())
+            .and_then(|v| v.trim().parse().ok());
+        let ip = forwarded
+            .or_else(|| {
+                extensions
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|ConnectInfo(addr)| addr.ip())
+            })
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        Self(ip)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let trust_proxy = state.config.server.trust_proxy;
+        Ok(Self::extract(
+            &parts.headers,
+            &parts.extensions,
+            trust_proxy,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    #[test]
+    fn client_ip_should_work() {
+        let req = Request::builder()
+            .header(FORWARDED_FOR_HEADER, "203.0.113.7, 10.0.0.1")
+            .body(())
+            .unwrap();
+        let (mut parts, _) = req.into_parts();
+        parts
+            .extensions
+            .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4000))));
+
+        let ip = ClientIp::extract(&parts.headers, &parts.extensions, true);
+        assert_eq!(ip.0, "203.0.113.7".parse::<IpAddr>().unwrap());
+        let ip = ClientIp::extract(&parts.headers, &parts.extensions, false);
+        assert_eq!(ip.0, "10.0.0.1".parse::<IpAddr>().unwrap());
+    }
+}
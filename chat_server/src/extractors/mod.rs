@@ -0,0 +1,5 @@
+mod client_ip;
+mod valid_json;
+
+pub use client_ip::ClientIp;
+pub use valid_json::ValidJson;
@@ -0,0 +1,84 @@
+use crate::{validation::Validate, AppError};
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+/// A JSON body that passed the rules of its type, rejected with per-field errors otherwise
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(input) = Json::<T>::from_request(req, state).await?;
+        input.check()?;
+        Ok(Self(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::CreateUser, ErrorOutput};
+    use anyhow::Result;
+    use axum::{
+        body::Body,
+        http::{header::CONTENT_TYPE, StatusCode},
+        response::IntoResponse,
+        routing::post,
+        Router,
+    };
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn signup(req: &str) -> Result<(StatusCode, ErrorOutput)> {
+        let app = Router::new().route(
+            "/signup",
+            post(|ValidJson(_): ValidJson<CreateUser>| async { StatusCode::CREATED }),
+        );
+        let req = Request::post("/signup")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(req.to_string()))?;
+        let res = app.oneshot(req).await?.into_response();
+        let status = res.status();
+        let body = res.into_body().collect().await?.to_bytes();
+        let output = serde_json::from_slice(&body).unwrap_or(ErrorOutput::new("", ""));
+        Ok((status, output))
+    }
+
+    #[tokio::test]
+    async fn valid_json_should_reject_invalid_fields() -> Result<()> {
+        let (status, _) = signup(
+            r#"{"fullname": "Tyr Chen", "email": "tchen@acme.org", "password": "Hunter42"}"#,
+        )
+        .await?;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let email = format!("{}@acme.org", "t".repeat(200));
+        let (status, output) = signup(&format!(
+            r#"{{"fullname": " ", "email": "{}", "password": ""}}"#,
+            email
+        ))
+        .await?;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(output.code, "validation_failed");
+        let fields = &output.details.expect("details")["fields"];
+        assert_eq!(fields["fullname"][0], "must not be empty");
+        assert_eq!(fields["email"][0], "must be at most 64 characters");
+        assert!(fields["password"].is_array());
+
+        let (status, output) = signup(r#"{"fullname": "Tyr Chen"}"#).await?;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(output.code, "invalid_json");
+        Ok(())
+    }
+}
@@ -1,41 +1,230 @@
 use crate::{
-    models::{CreateUser, SigninUser},
+    extractors::ClientIp,
+    extractors::ValidJson,
+    mailer::Mail,
+    models::{
+        CreateUser, ForgotPassword, ResendVerification, ResetPassword, SigninAttempt, SigninUser,
+        VerifyEmail, EMAIL_VERIFICATION_TTL_HOURS, PASSWORD_RESET_TTL_MINUTES,
+    },
     AppError, AppState, ErrorOutput, User,
 };
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use metrics::counter;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct AuthOutput {
-    token: String,
+    pub(crate) token: String,
+}
+
+/// Returned by signin when the account has two-factor authentication enabled, the token must be
+/// exchanged together with a code at /api/signin/2fa
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaPendingOutput {
+    pub(crate) mfa_token: String,
 }
 
+/// Create an account, a verification mail is sent to the email
+#[utoipa::path(
+    post,
+    path = "/api/signup",
+    tag = "auth",
+    security(()),
+    request_body = CreateUser,
+    responses(
+        (status = 201, description = "Signed up", body = AuthOutput),
+        (status = 409, description = "Email already exists", body = ErrorOutput),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
 pub(crate) async fn signup_handler(
     State(state): State<AppState>,
-    Json(input): Json<CreateUser>,
+    ValidJson(input): ValidJson<CreateUser>,
 ) -> Result<impl IntoResponse, AppError> {
     let user = User::create(&input, &state.pool).await?;
-    let token = state.ek.sign(user)?;
+    counter!("signups_total").increment(1);
+    send_verification_mail(&state, &user).await?;
+    let token = state.keys().ek.sign(user)?;
     let body = Json(AuthOutput { token });
     Ok((StatusCode::CREATED, body))
 }
 
+/// Sign in with email and password
+#[utoipa::path(
+    post,
+    path = "/api/signin",
+    tag = "auth",
+    security(()),
+    request_body = SigninUser,
+    responses(
+        (status = 200, description = "Signed in, or `MfaPendingOutput` when 2fa is enabled", body = AuthOutput),
+        (status = 403, description = "Invalid email or password", body = ErrorOutput),
+        (status = 429, description = "Too many failed attempts", body = ErrorOutput),
+    )
+)]
 pub(crate) async fn signin_handler(
     State(state): State<AppState>,
-    Json(input): Json<SigninUser>,
+    ClientIp(ip): ClientIp,
+    ValidJson(input): ValidJson<SigninUser>,
 ) -> Result<impl IntoResponse, AppError> {
+    let attempt = SigninAttempt::new(&input.email, ip);
+    if let Some(secs) = attempt.retry_after(&state.pool).await? {
+        return Err(AppError::TooManyRequests(secs));
+    }
     let user = User::verify(&input, &state.pool).await?;
 
     match user {
         Some(user) => {
-            let token = state.ek.sign(user)?;
+            attempt.reset(&state.pool).await?;
+            if User::is_totp_enabled(user.id, &state.pool).await? {
+                let mfa_token = state.keys().ek.sign_mfa(user)?;
+                let body = Json(MfaPendingOutput { mfa_token });
+                return Ok((StatusCode::OK, body).into_response());
+            }
+            let token = state.keys().ek.sign(user)?;
             Ok((StatusCode::OK, Json(AuthOutput { token })).into_response())
         }
         None => {
-            let body = Json(ErrorOutput::new("Invalid email or password"));
-            Ok((StatusCode::FORBIDDEN, body).into_response())
+            let throttle = &state.config.auth.signin_throttle;
+            attempt.record_failure(throttle, &state.pool).await?;
+            let body = ErrorOutput::new("invalid_credentials", "Invalid email or password");
+            Ok(body.into_response_with(StatusCode::FORBIDDEN))
+        }
+    }
+}
+
+/// Mail a password reset link, accepted whether or not the account exists
+#[utoipa::path(
+    post,
+    path = "/api/forgot-password",
+    tag = "auth",
+    security(()),
+    request_body = ForgotPassword,
+    responses((status = 202, description = "Accepted"))
+)]
+pub(crate) async fn forgot_password_handler(
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<ForgotPassword>,
+) -> Result<impl IntoResponse, AppError> {
+    // always accept the request so the response doesn't reveal whether the account exists
+    if let Some(user) = User::find_by_email(&input.email, &state.pool).await? {
+        let token = user.create_password_reset(&state.pool).await?;
+        let mail = Mail {
+            to: user.email.clone(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Hi {},\n\nUse the link below to reset your password, it expires in {} minutes.\n\n\
+                {}/reset-password?token={}\n\nIf you didn't request this, you can ignore this email.\n",
+                user.fullname, PASSWORD_RESET_TTL_MINUTES, state.config.mail.base_url, token
+            ),
+        };
+        let mailer = state.mailer.clone();
+        tokio::spawn(async move {
+            if let Err(e) = mailer.send(mail).await {
+                warn!("send password reset mail to {} failed: {}", user.email, e);
+            }
+        });
+    }
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Set a new password with the token of a reset link
+#[utoipa::path(
+    post,
+    path = "/api/reset-password",
+    tag = "auth",
+    security(()),
+    request_body = ResetPassword,
+    responses(
+        (status = 204, description = "Password changed"),
+        (status = 400, description = "Invalid or expired token", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn reset_password_handler(
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<ResetPassword>,
+) -> Result<impl IntoResponse, AppError> {
+    if User::reset_password(&input, &state.pool).await? {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        let body = ErrorOutput::new("invalid_token", "Invalid or expired token");
+        Ok(body.into_response_with(StatusCode::BAD_REQUEST))
+    }
+}
+
+/// Verify the email with the token of a verification link
+#[utoipa::path(
+    get,
+    path = "/api/verify-email",
+    tag = "auth",
+    security(()),
+    params(VerifyEmail),
+    responses(
+        (status = 200, description = "Email verified", body = String),
+        (status = 400, description = "Invalid or expired token", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn verify_email_handler(
+    State(state): State<AppState>,
+    Query(input): Query<VerifyEmail>,
+) -> Result<impl IntoResponse, AppError> {
+    match User::verify_email(&input.token, &state.pool).await? {
+        Some(_) => Ok((StatusCode::OK, "Email verified").into_response()),
+        None => {
+            let body = ErrorOutput::new("invalid_token", "Invalid or expired token");
+            Ok(body.into_response_with(StatusCode::BAD_REQUEST))
+        }
+    }
+}
+
+/// Send the verification mail again, accepted whether or not the account exists
+#[utoipa::path(
+    post,
+    path = "/api/resend-verification",
+    tag = "auth",
+    security(()),
+    request_body = ResendVerification,
+    responses((status = 202, description = "Accepted"))
+)]
+pub(crate) async fn resend_verification_handler(
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<ResendVerification>,
+) -> Result<impl IntoResponse, AppError> {
+    // always accept the request so the response doesn't reveal whether the account exists
+    if let Some(user) = User::find_by_email(&input.email, &state.pool).await? {
+        if user.email_verified_at.is_none() {
+            send_verification_mail(&state, &user).await?;
         }
     }
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn send_verification_mail(state: &AppState, user: &User) -> Result<(), AppError> {
+    let token = user.create_email_verification(&state.pool).await?;
+    let mail = Mail {
+        to: user.email.clone(),
+        subject: "Verify your email".to_string(),
+        body: format!(
+            "Hi {},\n\nPlease confirm your email address by opening the link below, it expires in \
+            {} hours.\n\n{}/api/verify-email?token={}\n",
+            user.fullname, EMAIL_VERIFICATION_TTL_HOURS, state.config.mail.base_url, token
+        ),
+    };
+    let mailer = state.mailer.clone();
+    tokio::spawn(async move {
+        let to = mail.to.clone();
+        if let Err(e) = mailer.send(mail).await {
+            warn!("send verification mail to {} failed: {}", to, e);
+        }
+    });
+    Ok(())
 }
 
 #[cfg(test)]
@@ -44,13 +233,18 @@ mod tests {
     use crate::AppConfig;
     use anyhow::Result;
     use http_body_util::BodyExt;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn localhost() -> IpAddr {
+        IpAddr::V4(Ipv4Addr::LOCALHOST)
+    }
 
     #[tokio::test]
     async fn signup_should_work() -> Result<()> {
         let config = AppConfig::load()?;
         let (_tdb, state) = AppState::new_for_test(config).await?;
         let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
-        let ret = signup_handler(State(state), Json(input))
+        let ret = signup_handler(State(state), ValidJson(input))
             .await?
             .into_response();
         assert_eq!(ret.status(), StatusCode::CREATED);
@@ -65,14 +259,15 @@ mod tests {
         let config = AppConfig::load()?;
         let (_tdb, state) = AppState::new_for_test(config).await?;
         let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
-        signup_handler(State(state.clone()), Json(input.clone())).await?;
-        let ret = signup_handler(State(state.clone()), Json(input.clone()))
+        signup_handler(State(state.clone()), ValidJson(input.clone())).await?;
+        let ret = signup_handler(State(state.clone()), ValidJson(input.clone()))
             .await
             .into_response();
         assert_eq!(ret.status(), StatusCode::CONFLICT);
         let body = ret.into_body().collect().await?.to_bytes();
         let ret: ErrorOutput = serde_json::from_slice(&body)?;
 
+        assert_eq!(ret.code, "email_already_exists");
         assert_eq!(ret.error, "email already exists: tchen@acme.org");
         Ok(())
     }
@@ -87,7 +282,7 @@ mod tests {
         let user = CreateUser::new(name, email, password);
         User::create(&user, &state.pool).await?;
         let input = SigninUser::new(email, password);
-        let ret = signin_handler(State(state), Json(input))
+        let ret = signin_handler(State(state), ClientIp(localhost()), ValidJson(input))
             .await?
             .into_response();
         assert_eq!(ret.status(), StatusCode::OK);
@@ -105,14 +300,118 @@ mod tests {
         let email = "alice@acme.org";
         let password = "Hunter42";
         let input = SigninUser::new(email, password);
-        let ret = signin_handler(State(state), Json(input))
+        let ret = signin_handler(State(state), ClientIp(localhost()), ValidJson(input))
             .await
             .into_response();
         assert_eq!(ret.status(), StatusCode::FORBIDDEN);
         let body = ret.into_body().collect().await?.to_bytes();
         let ret: ErrorOutput = serde_json::from_slice(&body)?;
+        assert_eq!(ret.code, "invalid_credentials");
         assert_eq!(ret.error, "Invalid email or password");
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn repeated_signin_failures_should_429() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let user = CreateUser::new("Alice", "alice@acme.org", "Hunter42");
+        User::create(&user, &state.pool).await?;
+
+        let input = SigninUser::new("alice@acme.org", "Hunter41");
+        let ret = signin_handler(
+            State(state.clone()),
+            ClientIp(localhost()),
+            ValidJson(input),
+        )
+        .await
+        .into_response();
+        assert_eq!(ret.status(), StatusCode::FORBIDDEN);
+
+        // even the right password has to wait for the backoff
+        let input = SigninUser::new("alice@acme.org", "Hunter42");
+        let ret = signin_handler(
+            State(state.clone()),
+            ClientIp(localhost()),
+            ValidJson(input),
+        )
+        .await
+        .into_response();
+        assert_eq!(ret.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(ret.headers().contains_key("retry-after"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verify_email_should_work() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Alice", "alice@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        let token = user.create_email_verification(&state.pool).await?;
+
+        let input = VerifyEmail { token };
+        let ret = verify_email_handler(State(state.clone()), Query(input.clone()))
+            .await?
+            .into_response();
+        assert_eq!(ret.status(), StatusCode::OK);
+        let ret = verify_email_handler(State(state.clone()), Query(input))
+            .await?
+            .into_response();
+        assert_eq!(ret.status(), StatusCode::BAD_REQUEST);
+
+        let user = User::find_by_email("alice@acme.org", &state.pool).await?;
+        assert!(user.is_some_and(|u| u.email_verified_at.is_some()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn forgot_password_should_not_reveal_account() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let user = CreateUser::new("Alice", "alice@acme.org", "Hunter42");
+        User::create(&user, &state.pool).await?;
+
+        for email in ["alice@acme.org", "bob@acme.org"] {
+            let input = ForgotPassword {
+                email: email.to_string(),
+            };
+            let ret = forgot_password_handler(State(state.clone()), ValidJson(input))
+                .await?
+                .into_response();
+            assert_eq!(ret.status(), StatusCode::ACCEPTED);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reset_password_should_work() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Alice", "alice@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        let token = user.create_password_reset(&state.pool).await?;
+
+        let input = ResetPassword {
+            token,
+            password: "Hunter43".to_string(),
+        };
+        let ret = reset_password_handler(State(state.clone()), ValidJson(input.clone()))
+            .await?
+            .into_response();
+        assert_eq!(ret.status(), StatusCode::NO_CONTENT);
+
+        let ret = reset_password_handler(State(state.clone()), ValidJson(input))
+            .await?
+            .into_response();
+        assert_eq!(ret.status(), StatusCode::BAD_REQUEST);
+
+        let input = SigninUser::new("alice@acme.org", "Hunter43");
+        let ret = signin_handler(State(state), ClientIp(localhost()), ValidJson(input))
+            .await?
+            .into_response();
+        assert_eq!(ret.status(), StatusCode::OK);
+        Ok(())
+    }
 }
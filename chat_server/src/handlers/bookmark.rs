@@ -0,0 +1,128 @@
+use crate::{
+    models::{Bookmark, CreateBookmark, ListMessages},
+    validation::Validate,
+    AppError, AppState, ErrorOutput, User,
+};
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+/// Bookmark a message, the body is optional
+#[utoipa::path(
+    post,
+    path = "/api/bookmarks/{message_id}",
+    tag = "messages",
+    params(("message_id" = i64, Path, description = "Message id")),
+    request_body(content = Option<CreateBookmark>),
+    responses(
+        (status = 201, description = "Bookmarked", body = Bookmark),
+        (status = 404, description = "Message not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn create_bookmark_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(message_id): Path<i64>,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    // the note is optional, so is the body, but a body that isn't a CreateBookmark is rejected
+    let input = if body.is_empty() {
+        CreateBookmark::default()
+    } else {
+        Json::<CreateBookmark>::from_bytes(&body)?.0
+    };
+    input.check()?;
+    let bookmark = Bookmark::create(user.id, message_id, &input, &state.pool).await?;
+    Ok((StatusCode::CREATED, Json(bookmark)))
+}
+
+/// List the bookmarks of the user, newest first
+#[utoipa::path(
+    get,
+    path = "/api/bookmarks",
+    tag = "messages",
+    params(ListMessages),
+    responses((status = 200, description = "Bookmarks", body = Vec<Bookmark>))
+)]
+pub(crate) async fn list_bookmarks_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Query(input): Query<ListMessages>,
+) -> Result<impl IntoResponse, AppError> {
+    let bookmarks = Bookmark::list(user.id, &input, &state.pool).await?;
+    Ok(Json(bookmarks))
+}
+
+/// Remove a bookmark
+#[utoipa::path(
+    delete,
+    path = "/api/bookmarks/{message_id}",
+    tag = "messages",
+    params(("message_id" = i64, Path, description = "Message id")),
+    responses(
+        (status = 204, description = "Removed"),
+        (status = 404, description = "Bookmark not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn delete_bookmark_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(message_id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    Bookmark::delete(user.id, message_id, &state.pool).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{CreateMessage, CreateUser, Message},
+        AppConfig,
+    };
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn create_bookmark_should_reject_malformed_body() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        sqlx::query("INSERT INTO chats (name, type, members) VALUES ('general', 'group', $1)")
+            .bind(vec![user.id])
+            .execute(&state.pool)
+            .await?;
+        let base_dir = &state.config.server.base_dir;
+        let message = Message::create(
+            &CreateMessage::new("hello"),
+            1,
+            user.id,
+            base_dir,
+            &state.pool,
+        )
+        .await?;
+
+        let create = |body: &'static str| {
+            create_bookmark_handler(
+                Extension(user.clone()),
+                State(state.clone()),
+                Path(message.id),
+                Bytes::from(body),
+            )
+        };
+        let res = create("").await?.into_response();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let res = create(r#"{"note": "read later"}"#).await?.into_response();
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        let ret = create(r#"{"note": 42}"#).await;
+        assert!(matches!(ret, Err(AppError::JsonRejection(_))));
+        let ret = create("read later").await;
+        assert!(matches!(ret, Err(AppError::JsonRejection(_))));
+        Ok(())
+    }
+}
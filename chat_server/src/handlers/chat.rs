@@ -1,17 +1,135 @@
-use axum::response::IntoResponse;
+use crate::{
+    extractors::ValidJson,
+    models::{Chat, Pin, UpdateChat},
+    AppError, AppState, ErrorOutput, User,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
 
+/// List the chats of the user
+#[utoipa::path(
+    get,
+    path = "/api/chat",
+    tag = "chats",
+    responses((status = 200, description = "Chats"))
+)]
 pub(crate) async fn list_chat_handler() -> impl IntoResponse {
     "chat"
 }
 
+/// Create a chat
+#[utoipa::path(
+    post,
+    path = "/api/chat",
+    tag = "chats",
+    responses((status = 200, description = "Created"))
+)]
 pub(crate) async fn create_chat_handler() -> impl IntoResponse {
     "create chat"
 }
 
-pub(crate) async fn update_chat_handler() -> impl IntoResponse {
-    "update chat"
+/// Rename a chat or change its topic and description
+#[utoipa::path(
+    patch,
+    path = "/api/chat/{id}",
+    tag = "chats",
+    params(("id" = i64, Path, description = "Chat id")),
+    request_body = UpdateChat,
+    responses(
+        (status = 200, description = "Updated chat", body = Chat),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Chat not found", body = ErrorOutput),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn update_chat_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    ValidJson(input): ValidJson<UpdateChat>,
+) -> Result<impl IntoResponse, AppError> {
+    let base_dir = &state.config.server.base_dir;
+    let chat = Chat::update(id, &user, &input, base_dir, &state.pool).await?;
+    Ok(Json(chat))
 }
 
+/// Delete a chat
+#[utoipa::path(
+    delete,
+    path = "/api/chat/{id}",
+    tag = "chats",
+    params(("id" = i64, Path, description = "Chat id")),
+    responses((status = 200, description = "Deleted"))
+)]
 pub(crate) async fn delete_chat_handler() -> impl IntoResponse {
     "delete chat"
 }
+
+/// List the pinned messages of a chat
+#[utoipa::path(
+    get,
+    path = "/api/chat/{id}/pins",
+    tag = "chats",
+    params(("id" = i64, Path, description = "Chat id")),
+    responses(
+        (status = 200, description = "Pins, newest first", body = Vec<Pin>),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Chat not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn list_pins_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let pins = Pin::list(id, user.id, &state.pool).await?;
+    Ok(Json(pins))
+}
+
+/// Pin a message of the chat
+#[utoipa::path(
+    post,
+    path = "/api/chat/{id}/pins/{message_id}",
+    tag = "chats",
+    params(("id" = i64, Path, description = "Chat id"), ("message_id" = i64, Path, description = "Message id")),
+    responses(
+        (status = 201, description = "Pinned", body = Pin),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Chat not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn pin_message_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path((id, message_id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse, AppError> {
+    let max_pins = state.config.chat.max_pins;
+    let base_dir = &state.config.server.base_dir;
+    let pin = Pin::create(id, message_id, &user, max_pins, base_dir, &state.pool).await?;
+    Ok((StatusCode::CREATED, Json(pin)))
+}
+
+/// Unpin a message
+#[utoipa::path(
+    delete,
+    path = "/api/chat/{id}/pins/{message_id}",
+    tag = "chats",
+    params(("id" = i64, Path, description = "Chat id"), ("message_id" = i64, Path, description = "Message id")),
+    responses(
+        (status = 204, description = "Unpinned"),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Pin not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn unpin_message_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path((id, message_id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse, AppError> {
+    Pin::delete(id, message_id, user.id, &state.pool).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,100 @@
+use crate::{
+    extractors::ValidJson,
+    models::{CreateSlashCommand, SlashCommand},
+    AppError, AppState, ErrorOutput, User,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A command with the secret its requests are signed with, only returned on creation
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub(crate) struct SlashCommandOutput {
+    #[serde(flatten)]
+    pub(crate) command: SlashCommand,
+    pub(crate) secret: String,
+}
+
+/// Add a slash command to a chat, answered by POSTs to its url
+#[utoipa::path(
+    post,
+    path = "/api/chat/{id}/commands",
+    tag = "integrations",
+    params(("id" = i64, Path, description = "Chat id")),
+    request_body = CreateSlashCommand,
+    responses(
+        (status = 201, description = "Created, with the signing secret", body = SlashCommandOutput),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Chat not found", body = ErrorOutput),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn create_command_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    ValidJson(input): ValidJson<CreateSlashCommand>,
+) -> Result<impl IntoResponse, AppError> {
+    let reserved = state.commands.builtin_names();
+    let command = SlashCommand::create(
+        id,
+        &user,
+        &input,
+        &reserved,
+        &state.config.outbound,
+        &state.pool,
+    )
+    .await?;
+    let secret = command.secret.clone();
+    Ok((
+        StatusCode::CREATED,
+        Json(SlashCommandOutput { command, secret }),
+    ))
+}
+
+/// List the slash commands of a chat
+#[utoipa::path(
+    get,
+    path = "/api/chat/{id}/commands",
+    tag = "integrations",
+    params(("id" = i64, Path, description = "Chat id")),
+    responses(
+        (status = 200, description = "Commands", body = Vec<SlashCommand>),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Chat not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn list_commands_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let commands = SlashCommand::list(id, user.id, &state.pool).await?;
+    Ok(Json(commands))
+}
+
+/// Remove a slash command
+#[utoipa::path(
+    delete,
+    path = "/api/commands/{id}",
+    tag = "integrations",
+    params(("id" = i64, Path, description = "Command id")),
+    responses(
+        (status = 204, description = "Removed"),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Command not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn delete_command_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    SlashCommand::delete(id, user.id, &state.pool).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,91 @@
+use crate::AppState;
+use axum::{extract::State, response::IntoResponse};
+use chat_core::{check, db_check, readiness};
+use sqlx::migrate::Migrator;
+use std::collections::BTreeMap;
+
+pub(crate) use chat_core::health_handler;
+
+/// the migrations this build expects, the same ones the tests apply
+static MIGRATOR: Migrator = sqlx::migrate!("../migrations");
+
+/// Ready to take traffic: the db is reachable, migrations are applied, the keys are a pair and
+/// the server isn't shutting down
+pub(crate) async fn ready_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let mut checks = BTreeMap::new();
+    let shutdown = if state.shutdown.is_triggered() {
+        Err("server is shutting down".to_string())
+    } else {
+        Ok(())
+    };
+    checks.insert("shutdown".to_string(), check(async { shutdown }).await);
+    checks.insert("db".to_string(), check(db_check(&state.pool)).await);
+    checks.insert("migrations".to_string(), check(migrations(&state)).await);
+    let keys = check(async { state.keys().check().map_err(|e| e.to_string()) });
+    checks.insert("keys".to_string(), keys.await);
+    readiness(checks)
+}
+
+async fn migrations(state: &AppState) -> Result<(), String> {
+    let applied: Vec<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(&state.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    let pending: Vec<_> = MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+        .map(|m| m.version.to_string())
+        .collect();
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("pending migrations: {}", pending.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppConfig;
+    use anyhow::Result;
+    use axum::http::StatusCode;
+    use chat_core::{HealthOutput, HealthStatus};
+    use http_body_util::BodyExt;
+
+    async fn ready(state: &AppState) -> Result<(StatusCode, HealthOutput)> {
+        let res = ready_handler(State(state.clone())).await.into_response();
+        let status = res.status();
+        let body = res.into_body().collect().await?.to_bytes();
+        Ok((status, serde_json::from_slice(&body)?))
+    }
+
+    #[tokio::test]
+    async fn ready_handler_should_report_checks() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+
+        let (status, output) = ready(&state).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(output.status, HealthStatus::Ok);
+        assert_eq!(
+            output.checks.keys().collect::<Vec<_>>(),
+            vec!["db", "keys", "migrations", "shutdown"]
+        );
+
+        sqlx::query("DELETE FROM _sqlx_migrations WHERE version = (SELECT max(version) FROM _sqlx_migrations)")
+            .execute(&state.pool)
+            .await?;
+        state.shutdown.trigger();
+        let (status, output) = ready(&state).await?;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(output.status, HealthStatus::Unavailable);
+        assert_eq!(output.checks["db"].status, HealthStatus::Ok);
+        assert_eq!(
+            output.checks["migrations"].status,
+            HealthStatus::Unavailable
+        );
+        assert_eq!(output.checks["shutdown"].status, HealthStatus::Unavailable);
+        Ok(())
+    }
+}
@@ -1,9 +1,175 @@
-use axum::response::IntoResponse;
+use crate::{
+    commands::EphemeralOutput,
+    extractors::ValidJson,
+    models::{ChatFile, CreateMessage, ListMessages, Mention, Message},
+    AppError, AppState, ErrorOutput, User,
+};
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use tokio::fs;
+use tracing::{info, warn};
 
-pub(crate) async fn send_message_handler() -> impl IntoResponse {
-    "send message"
+/// Send a message, or run the slash command it starts with
+#[utoipa::path(
+    post,
+    path = "/api/chat/{id}",
+    tag = "messages",
+    params(("id" = i64, Path, description = "Chat id")),
+    request_body = CreateMessage,
+    responses(
+        (status = 201, description = "Sent message", body = Message),
+        (status = 200, description = "Reply of a command only the sender sees", body = EphemeralOutput),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Chat not found", body = ErrorOutput),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn send_message_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    ValidJson(mut input): ValidJson<CreateMessage>,
+) -> Result<impl IntoResponse, AppError> {
+    let commands = &state.commands;
+    if let Some(output) = commands.dispatch(&state, &user, id, &mut input).await? {
+        return Ok(output.into_response());
+    }
+    let base_dir = &state.config.server.base_dir;
+    let message = Message::create(&input, id, user.id, base_dir, &state.pool).await?;
+    Ok((StatusCode::CREATED, Json(message)).into_response())
 }
 
-pub(crate) async fn list_message_handler() -> impl IntoResponse {
-    "list message"
+/// List the messages of a chat, newest first
+#[utoipa::path(
+    get,
+    path = "/api/chat/{id}/messages",
+    tag = "messages",
+    params(("id" = i64, Path, description = "Chat id"), ListMessages),
+    responses(
+        (status = 200, description = "Messages", body = Vec<Message>),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Chat not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn list_message_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(input): Query<ListMessages>,
+) -> Result<impl IntoResponse, AppError> {
+    let messages = Message::list(&input, id, user.id, &state.pool).await?;
+    Ok(Json(messages))
+}
+
+/// List the messages mentioning the user, newest first
+#[utoipa::path(
+    get,
+    path = "/api/mentions",
+    tag = "messages",
+    params(ListMessages),
+    responses((status = 200, description = "Mentions", body = Vec<Mention>))
+)]
+pub(crate) async fn list_mentions_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Query(input): Query<ListMessages>,
+) -> Result<impl IntoResponse, AppError> {
+    let mentions = Mention::list(&input, user.id, &state.pool).await?;
+    Ok(Json(mentions))
+}
+
+/// Upload files to attach to messages
+#[utoipa::path(
+    post,
+    path = "/api/upload",
+    tag = "files",
+    request_body(content_type = "multipart/form-data", description = "One or more files"),
+    responses(
+        (status = 200, description = "Urls of the files", body = Vec<String>),
+        (status = 413, description = "Too many or too large files", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn upload_handler(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let base_dir = &state.config.server.base_dir;
+    let limits = state.upload.load_full();
+    let max_bytes = limits.max_file_mb as usize * 1024 * 1024;
+    let mut files = vec![];
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::InvalidInput(e.to_string()))?
+    {
+        let Some(filename) = field.file_name().map(|name| name.to_string()) else {
+            warn!("Failed to read multipart field");
+            continue;
+        };
+        if files.len() >= limits.max_files as usize {
+            return Err(AppError::PayloadTooLarge(format!(
+                "at most {} files per upload",
+                limits.max_files
+            )));
+        }
+        // read in chunks to stop as soon as the file is too large
+        let mut data = Vec::new();
+        while let Some(chunk) = field
+            .chunk()
+            .await
+            .map_err(|e| AppError::InvalidInput(e.to_string()))?
+        {
+            if data.len() + chunk.len() > max_bytes {
+                return Err(AppError::PayloadTooLarge(format!(
+                    "{} exceeds {} MB",
+                    filename, limits.max_file_mb
+                )));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        let file = ChatFile::new(&filename, &data);
+        let path = file.path(base_dir);
+        if path.exists() {
+            info!("File {} already exists: {:?}", filename, path);
+        } else {
+            fs::create_dir_all(path.parent().expect("file path parent should exists")).await?;
+            fs::write(path, data).await?;
+        }
+        files.push(file.url());
+    }
+
+    Ok(Json(files))
+}
+
+/// Download an uploaded file
+#[utoipa::path(
+    get,
+    path = "/api/files/{path}",
+    tag = "files",
+    params(("path" = String, Path, description = "Path of the url returned by the upload")),
+    responses(
+        (status = 200, description = "The file, typed by its extension"),
+        (status = 404, description = "File not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn file_handler(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let file: ChatFile = format!("/files/{}", path).parse()?;
+    let path = file.path(&state.config.server.base_dir);
+    if !path.exists() {
+        return Err(AppError::NotFound(format!("file {}", file.url())));
+    }
+
+    let mime = mime_guess::from_path(&path).first_or_octet_stream();
+    let body = fs::read(path).await?;
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, mime.to_string().parse()?);
+    Ok((headers, body))
 }
@@ -0,0 +1,173 @@
+use super::AuthOutput;
+use crate::{
+    extractors::ClientIp,
+    extractors::ValidJson,
+    models::{RecoveryCodes, SigninAttempt, SigninMfa, TotpCode, TotpEnrollment},
+    AppError, AppState, ErrorOutput, User,
+};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+
+/// Start two-factor enrollment, it is enabled once a code is confirmed
+#[utoipa::path(
+    post,
+    path = "/api/users/me/2fa",
+    tag = "auth",
+    responses((status = 200, description = "Secret for the authenticator app", body = TotpEnrollment))
+)]
+pub(crate) async fn enroll_totp_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let enrollment = user.enroll_totp(&state.pool).await?;
+    Ok(Json(enrollment))
+}
+
+/// Confirm enrollment with a code and get the recovery codes
+#[utoipa::path(
+    post,
+    path = "/api/users/me/2fa/confirm",
+    tag = "auth",
+    request_body = TotpCode,
+    responses(
+        (status = 200, description = "Two-factor authentication enabled", body = RecoveryCodes),
+        (status = 403, description = "Invalid code", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn confirm_totp_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<TotpCode>,
+) -> Result<impl IntoResponse, AppError> {
+    match user.confirm_totp(&input.code, &state.pool).await? {
+        Some(codes) => Ok((StatusCode::OK, Json(codes)).into_response()),
+        None => {
+            let body = ErrorOutput::new("invalid_code", "Invalid code");
+            Ok(body.into_response_with(StatusCode::FORBIDDEN))
+        }
+    }
+}
+
+/// Disable two-factor authentication
+#[utoipa::path(
+    delete,
+    path = "/api/users/me/2fa",
+    tag = "auth",
+    request_body = TotpCode,
+    responses(
+        (status = 204, description = "Disabled"),
+        (status = 403, description = "Invalid code", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn disable_totp_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<TotpCode>,
+) -> Result<impl IntoResponse, AppError> {
+    if user.disable_totp(&input.code, &state.pool).await? {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        let body = ErrorOutput::new("invalid_code", "Invalid code");
+        Ok(body.into_response_with(StatusCode::FORBIDDEN))
+    }
+}
+
+/// Finish signing in with the pending token and a code
+#[utoipa::path(
+    post,
+    path = "/api/signin/2fa",
+    tag = "auth",
+    security(()),
+    request_body = SigninMfa,
+    responses(
+        (status = 200, description = "Signed in", body = AuthOutput),
+        (status = 403, description = "Invalid code", body = ErrorOutput),
+        (status = 429, description = "Too many failed attempts", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn signin_mfa_handler(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    ValidJson(input): ValidJson<SigninMfa>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.keys().dk.verify_mfa(&input.mfa_token)?;
+    // guessing codes counts against the same limits as guessing passwords
+    let attempt = SigninAttempt::new(&user.email, ip);
+    if let Some(secs) = attempt.retry_after(&state.pool).await? {
+        return Err(AppError::TooManyRequests(secs));
+    }
+    if User::verify_second_factor(user.id, &input.code, &state.pool).await? {
+        let token = state.keys().ek.sign(user)?;
+        Ok((StatusCode::OK, Json(AuthOutput { token })).into_response())
+    } else {
+        let throttle = &state.config.auth.signin_throttle;
+        attempt.record_failure(throttle, &state.pool).await?;
+        let body = ErrorOutput::new("invalid_code", "Invalid code");
+        Ok(body.into_response_with(StatusCode::FORBIDDEN))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handlers::{signin_handler, MfaPendingOutput},
+        models::{CreateUser, SigninUser},
+        utils::Totp,
+        AppConfig,
+    };
+    use anyhow::Result;
+    use http_body_util::BodyExt;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[tokio::test]
+    async fn signin_with_totp_should_work() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let input = CreateUser::new("Alice", "alice@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        let enrollment = user.enroll_totp(&state.pool).await?;
+        let totp = Totp::from_secret(&enrollment.secret, &user.email)?;
+        let now = chrono::Utc::now().timestamp() as u64;
+        let input = TotpCode {
+            code: totp.code(now),
+        };
+        let ret = confirm_totp_handler(Extension(user), State(state.clone()), ValidJson(input))
+            .await?
+            .into_response();
+        assert_eq!(ret.status(), StatusCode::OK);
+
+        // password alone only yields a pending token
+        let input = SigninUser::new("alice@acme.org", "Hunter42");
+        let ret = signin_handler(State(state.clone()), ClientIp(localhost), ValidJson(input))
+            .await?
+            .into_response();
+        assert_eq!(ret.status(), StatusCode::OK);
+        let body = ret.into_body().collect().await?.to_bytes();
+        let ret: MfaPendingOutput = serde_json::from_slice(&body)?;
+        assert!(state.keys().dk.verify(&ret.mfa_token).is_err());
+
+        let mfa_token = ret.mfa_token;
+        let input = SigninMfa {
+            mfa_token: mfa_token.clone(),
+            code: totp.code(now + 30),
+        };
+        let res = signin_mfa_handler(State(state.clone()), ClientIp(localhost), ValidJson(input))
+            .await?
+            .into_response();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = res.into_body().collect().await?.to_bytes();
+        let ret: AuthOutput = serde_json::from_slice(&body)?;
+        assert_eq!(state.keys().dk.verify(&ret.token)?.email, "alice@acme.org");
+
+        let input = SigninMfa {
+            mfa_token,
+            code: "000000".to_string(),
+        };
+        let res = signin_mfa_handler(State(state.clone()), ClientIp(localhost), ValidJson(input))
+            .await?
+            .into_response();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        Ok(())
+    }
+}
@@ -1,18 +1,31 @@
 mod auth;
+mod bookmark;
 mod chat;
+mod command;
+mod health;
 mod messages;
+mod mfa;
+mod scheduled;
+mod search;
+mod token;
+mod user;
+mod webhook;
 
 use axum::response::IntoResponse;
-use axum::Router;
-use tower::ServiceBuilder;
-use tower_http::compression::CompressionLayer;
-use tower_http::LatencyUnit;
-use tower_http::trace::{DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, TraceLayer};
-use tracing::Level;
-pub (crate) use auth::*;
-pub (crate) use chat::*;
-pub (crate) use messages::*;
 
-pub (crate) async fn index_handler() -> impl IntoResponse{
+pub(crate) use auth::*;
+pub(crate) use bookmark::*;
+pub(crate) use chat::*;
+pub(crate) use command::*;
+pub(crate) use health::*;
+pub(crate) use messages::*;
+pub(crate) use mfa::*;
+pub(crate) use scheduled::*;
+pub(crate) use search::*;
+pub(crate) use token::*;
+pub(crate) use user::*;
+pub(crate) use webhook::*;
+
+pub(crate) async fn index_handler() -> impl IntoResponse {
     "index"
-}
\ No newline at end of file
+}
@@ -0,0 +1,81 @@
+use crate::{
+    extractors::ValidJson,
+    models::{CreateScheduledMessage, ListScheduledMessages, ScheduledMessage},
+    AppError, AppState, ErrorOutput, User,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+/// Schedule a message to be sent later
+#[utoipa::path(
+    post,
+    path = "/api/chat/{id}/scheduled",
+    tag = "messages",
+    params(("id" = i64, Path, description = "Chat id")),
+    request_body = CreateScheduledMessage,
+    responses(
+        (status = 201, description = "Scheduled", body = ScheduledMessage),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Chat not found", body = ErrorOutput),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn schedule_message_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    ValidJson(input): ValidJson<CreateScheduledMessage>,
+) -> Result<impl IntoResponse, AppError> {
+    let config = &state.config;
+    let scheduled = ScheduledMessage::create(
+        id,
+        user.id,
+        &input,
+        &config.scheduled_messages,
+        &config.server.base_dir,
+        &state.pool,
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(scheduled)))
+}
+
+/// List the scheduled messages of the user
+#[utoipa::path(
+    get,
+    path = "/api/scheduled",
+    tag = "messages",
+    params(ListScheduledMessages),
+    responses((status = 200, description = "Scheduled messages", body = Vec<ScheduledMessage>))
+)]
+pub(crate) async fn list_scheduled_messages_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Query(input): Query<ListScheduledMessages>,
+) -> Result<impl IntoResponse, AppError> {
+    let scheduled = ScheduledMessage::list(user.id, &input, &state.pool).await?;
+    Ok(Json(scheduled))
+}
+
+/// Cancel a pending scheduled message
+#[utoipa::path(
+    delete,
+    path = "/api/scheduled/{id}",
+    tag = "messages",
+    params(("id" = i64, Path, description = "Scheduled message id")),
+    responses(
+        (status = 200, description = "Cancelled", body = ScheduledMessage),
+        (status = 404, description = "No pending scheduled message", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn cancel_scheduled_message_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let scheduled = ScheduledMessage::cancel(id, user.id, &state.pool).await?;
+    Ok(Json(scheduled))
+}
@@ -0,0 +1,29 @@
+use crate::{
+    models::{SearchHit, SearchMessages},
+    AppError, AppState, ErrorOutput, User,
+};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Extension, Json,
+};
+
+/// Search the messages of the user's chats
+#[utoipa::path(
+    get,
+    path = "/api/search",
+    tag = "messages",
+    params(SearchMessages),
+    responses(
+        (status = 200, description = "Hits, best match first", body = Vec<SearchHit>),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn search_messages_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Query(input): Query<SearchMessages>,
+) -> Result<impl IntoResponse, AppError> {
+    let hits = input.search(user.id, &state.pool).await?;
+    Ok(Json(hits))
+}
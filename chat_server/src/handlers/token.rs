@@ -0,0 +1,164 @@
+use crate::{
+    extractors::ValidJson,
+    models::{ApiToken, ApiTokenAuth, CreateApiToken, CreateBot, CreatedApiToken},
+    AppError, AppState, ErrorOutput, User,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+/// Create a bot owned by the user, its tokens are issued with `bot_id`
+#[utoipa::path(
+    post,
+    path = "/api/bots",
+    tag = "integrations",
+    request_body = CreateBot,
+    responses(
+        (status = 201, description = "Created bot", body = User),
+        (status = 403, description = "Not allowed with an api token", body = ErrorOutput),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn create_bot_handler(
+    Extension(user): Extension<User>,
+    auth: Option<Extension<ApiTokenAuth>>,
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<CreateBot>,
+) -> Result<impl IntoResponse, AppError> {
+    require_session(&user, auth)?;
+    let bot = User::create_bot(user.id, &input, &mut *state.pool.acquire().await?).await?;
+    Ok((StatusCode::CREATED, Json(bot)))
+}
+
+/// List the bots of the user
+#[utoipa::path(
+    get,
+    path = "/api/bots",
+    tag = "integrations",
+    responses((status = 200, description = "Bots", body = Vec<User>))
+)]
+pub(crate) async fn list_bots_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let bots = User::list_bots(user.id, &state.pool).await?;
+    Ok(Json(bots))
+}
+
+/// Create an api token, the token is only returned here
+#[utoipa::path(
+    post,
+    path = "/api/tokens",
+    tag = "integrations",
+    request_body = CreateApiToken,
+    responses(
+        (status = 201, description = "Created token", body = CreatedApiToken),
+        (status = 403, description = "Not allowed with an api token", body = ErrorOutput),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn create_token_handler(
+    Extension(user): Extension<User>,
+    auth: Option<Extension<ApiTokenAuth>>,
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<CreateApiToken>,
+) -> Result<impl IntoResponse, AppError> {
+    require_session(&user, auth)?;
+    let token = ApiToken::create(&user, &input, &state.pool).await?;
+    Ok((StatusCode::CREATED, Json(token)))
+}
+
+/// List the api tokens of the user
+#[utoipa::path(
+    get,
+    path = "/api/tokens",
+    tag = "integrations",
+    responses((status = 200, description = "Tokens", body = Vec<ApiToken>))
+)]
+pub(crate) async fn list_tokens_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let tokens = ApiToken::list(user.id, &state.pool).await?;
+    Ok(Json(tokens))
+}
+
+/// Revoke an api token
+#[utoipa::path(
+    delete,
+    path = "/api/tokens/{id}",
+    tag = "integrations",
+    params(("id" = i64, Path, description = "Token id")),
+    responses(
+        (status = 204, description = "Revoked"),
+        (status = 403, description = "Not allowed with an api token", body = ErrorOutput),
+        (status = 404, description = "Token not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn revoke_token_handler(
+    Extension(user): Extension<User>,
+    auth: Option<Extension<ApiTokenAuth>>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    require_session(&user, auth)?;
+    ApiToken::revoke(user.id, id, &state.pool).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// a leaked token must not be able to mint or revoke other tokens
+fn require_session(user: &User, auth: Option<Extension<ApiTokenAuth>>) -> Result<(), AppError> {
+    if auth.is_some() || user.is_bot {
+        return Err(AppError::PermissionDenied(
+            "managing bots and tokens requires a signed in user".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::CreateUser, AppConfig};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn token_management_should_require_session() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+
+        let input = CreateApiToken {
+            name: "ci".to_string(),
+            scopes: vec!["write".to_string()],
+            ..Default::default()
+        };
+        let ret = create_token_handler(
+            Extension(user.clone()),
+            None,
+            State(state.clone()),
+            ValidJson(input.clone()),
+        )
+        .await?
+        .into_response();
+        assert_eq!(ret.status(), StatusCode::CREATED);
+
+        let auth = ApiTokenAuth {
+            token_id: 1,
+            scopes: vec!["write".to_string()],
+        };
+        let ret = create_token_handler(
+            Extension(user),
+            Some(Extension(auth)),
+            State(state.clone()),
+            ValidJson(input),
+        )
+        .await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        Ok(())
+    }
+}
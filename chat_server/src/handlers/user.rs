@@ -0,0 +1,253 @@
+use crate::{
+    extractors::ValidJson,
+    models::{ChangePassword, ChatFile, ListUsers, UpdateUser},
+    AppError, AppState, ErrorOutput, User,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+/// Find users sharing a chat with the current user by a prefix of their name or email
+#[utoipa::path(
+    get,
+    path = "/api/users",
+    tag = "users",
+    params(ListUsers),
+    responses(
+        (status = 200, description = "Users", body = Vec<User>),
+        (status = 400, description = "Empty query", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn list_users_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Query(input): Query<ListUsers>,
+) -> Result<impl IntoResponse, AppError> {
+    let users = User::list(user.id, &input, &state.pool).await?;
+    Ok(Json(users))
+}
+
+/// Get a user
+#[utoipa::path(
+    get,
+    path = "/api/users/{id}",
+    tag = "users",
+    params(("id" = i64, Path, description = "User id")),
+    responses(
+        (status = 200, description = "The user", body = User),
+        (status = 404, description = "User not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn get_user_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = User::find_by_id(id, &state.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("user {}", id)))?;
+    Ok(Json(user))
+}
+
+/// Update the profile of the user
+#[utoipa::path(
+    patch,
+    path = "/api/users/me",
+    tag = "users",
+    request_body = UpdateUser,
+    responses(
+        (status = 200, description = "Updated user", body = User),
+        (status = 404, description = "Avatar file not found", body = ErrorOutput),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn update_profile_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    ValidJson(mut input): ValidJson<UpdateUser>,
+) -> Result<impl IntoResponse, AppError> {
+    // avatar must be a file previously uploaded through /api/upload
+    if let Some(avatar_url) = input.avatar_url.as_deref().filter(|v| !v.is_empty()) {
+        let file: ChatFile = avatar_url.parse()?;
+        if !file.path(&state.config.server.base_dir).exists() {
+            return Err(AppError::NotFound(format!("file {}", avatar_url)));
+        }
+        input.avatar_url = Some(file.url());
+    }
+    let user = User::update(user.id, &input, &state.pool).await?;
+    Ok(Json(user))
+}
+
+/// Change the password of the user
+#[utoipa::path(
+    put,
+    path = "/api/users/me/password",
+    tag = "users",
+    request_body = ChangePassword,
+    responses(
+        (status = 204, description = "Changed"),
+        (status = 403, description = "Current password is incorrect", body = ErrorOutput),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn change_password_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<ChangePassword>,
+) -> Result<impl IntoResponse, AppError> {
+    if !User::change_password(user.id, &input, &state.pool).await? {
+        return Err(AppError::PermissionDenied(
+            "current password is incorrect".to_string(),
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::CreateUser, AppConfig};
+    use anyhow::Result;
+    use http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn list_users_should_match_prefix() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let mut users = Vec::new();
+        for (name, email) in [
+            ("Tyr Chen", "tchen@acme.org"),
+            ("Alice Chen", "alice@acme.org"),
+            ("Alan Hua", "alan@acme.org"),
+            ("Albot", "albot@acme.org"),
+        ] {
+            users.push(User::create(&CreateUser::new(name, email, "Hunter42"), &state.pool).await?);
+        }
+        sqlx::query("UPDATE users SET is_bot = true WHERE email = 'albot@acme.org'")
+            .execute(&state.pool)
+            .await?;
+        // alan doesn't share a chat with tyr
+        sqlx::query(
+            "INSERT INTO chats (name, type, members) VALUES ('general', 'group', '{1,2,4}')",
+        )
+        .execute(&state.pool)
+        .await?;
+        let user = users[0].clone();
+
+        let input = ListUsers {
+            q: Some("al".to_string()),
+            ..Default::default()
+        };
+        let ret = list_users_handler(Extension(user.clone()), State(state.clone()), Query(input))
+            .await?
+            .into_response();
+        assert_eq!(ret.status(), StatusCode::OK);
+        let body = ret.into_body().collect().await?.to_bytes();
+        let users: Vec<User> = serde_json::from_slice(&body)?;
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].email, "alice@acme.org");
+
+        // wildcards are matched literally
+        let input = ListUsers {
+            q: Some("%".to_string()),
+            ..Default::default()
+        };
+        let users = User::list(user.id, &input, &state.pool).await?;
+        assert!(users.is_empty());
+
+        let input = ListUsers {
+            q: Some(" ".to_string()),
+            ..Default::default()
+        };
+        let ret = User::list(user.id, &input, &state.pool).await;
+        assert!(matches!(ret, Err(AppError::InvalidInput(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_profile_should_work() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+
+        let input = UpdateUser {
+            status_text: Some("on vacation".to_string()),
+            timezone: Some("Asia/Shanghai".to_string()),
+            ..Default::default()
+        };
+        let ret = update_profile_handler(
+            Extension(user.clone()),
+            State(state.clone()),
+            ValidJson(input),
+        )
+        .await?
+        .into_response();
+        assert_eq!(ret.status(), StatusCode::OK);
+
+        let ret = get_user_handler(State(state.clone()), Path(user.id))
+            .await?
+            .into_response();
+        let body = ret.into_body().collect().await?.to_bytes();
+        let ret: User = serde_json::from_slice(&body)?;
+        assert_eq!(ret.fullname, "Tyr Chen");
+        assert_eq!(ret.status_text.as_deref(), Some("on vacation"));
+        assert_eq!(ret.timezone.as_deref(), Some("Asia/Shanghai"));
+
+        let input = UpdateUser {
+            timezone: Some("Mars/Olympus".to_string()),
+            ..Default::default()
+        };
+        let ret = update_profile_handler(
+            Extension(user.clone()),
+            State(state.clone()),
+            ValidJson(input),
+        )
+        .await
+        .into_response();
+        assert_eq!(ret.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let input = UpdateUser {
+            avatar_url: Some("/files/abc/def/0123456789abcdef0123456789abcdef01.png".to_string()),
+            ..Default::default()
+        };
+        let ret = update_profile_handler(Extension(user), State(state), ValidJson(input))
+            .await
+            .into_response();
+        assert_eq!(ret.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn change_password_with_wrong_password_should_403() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+
+        let input = ChangePassword {
+            current_password: "Hunter41".to_string(),
+            new_password: "Hunter43".to_string(),
+        };
+        let ret = change_password_handler(
+            Extension(user.clone()),
+            State(state.clone()),
+            ValidJson(input),
+        )
+        .await
+        .into_response();
+        assert_eq!(ret.status(), StatusCode::FORBIDDEN);
+
+        let input = ChangePassword {
+            current_password: "Hunter42".to_string(),
+            new_password: "Hunter43".to_string(),
+        };
+        let ret = change_password_handler(Extension(user), State(state), ValidJson(input))
+            .await?
+            .into_response();
+        assert_eq!(ret.status(), StatusCode::NO_CONTENT);
+        Ok(())
+    }
+}
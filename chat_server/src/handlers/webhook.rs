@@ -0,0 +1,330 @@
+use crate::{
+    extractors::ValidJson,
+    middlewares::RateLimitKey,
+    models::{
+        CreateIncomingWebhook, CreateOutgoingWebhook, IncomingWebhook, ListDeliveries, Message,
+        OutgoingWebhook, WebhookDelivery, WebhookPayload,
+    },
+    AppError, AppState, ErrorOutput, User,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A webhook with its url, the only time the secret is returned
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub(crate) struct IncomingWebhookOutput {
+    #[serde(flatten)]
+    pub(crate) webhook: IncomingWebhook,
+    pub(crate) url: String,
+}
+
+/// An outgoing webhook with its signing secret, only returned on creation
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub(crate) struct OutgoingWebhookOutput {
+    #[serde(flatten)]
+    pub(crate) webhook: OutgoingWebhook,
+    pub(crate) secret: String,
+}
+
+/// Create an incoming webhook posting to a chat
+#[utoipa::path(
+    post,
+    path = "/api/chat/{id}/webhooks",
+    tag = "integrations",
+    params(("id" = i64, Path, description = "Chat id")),
+    request_body = CreateIncomingWebhook,
+    responses(
+        (status = 201, description = "Created, with its url", body = IncomingWebhookOutput),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Chat not found", body = ErrorOutput),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn create_webhook_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    ValidJson(input): ValidJson<CreateIncomingWebhook>,
+) -> Result<impl IntoResponse, AppError> {
+    let (webhook, token) = IncomingWebhook::create(id, &user, &input, &state.pool).await?;
+    let output = IncomingWebhookOutput::new(&state, webhook, &token);
+    Ok((StatusCode::CREATED, Json(output)))
+}
+
+/// List the incoming webhooks of a chat
+#[utoipa::path(
+    get,
+    path = "/api/chat/{id}/webhooks",
+    tag = "integrations",
+    params(("id" = i64, Path, description = "Chat id")),
+    responses(
+        (status = 200, description = "Webhooks", body = Vec<IncomingWebhook>),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Chat not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn list_webhooks_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let webhooks = IncomingWebhook::list(id, user.id, &state.pool).await?;
+    Ok(Json(webhooks))
+}
+
+/// Replace the url of an incoming webhook, the old one stops working
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/{id}/regenerate",
+    tag = "integrations",
+    params(("id" = i64, Path, description = "Webhook id")),
+    responses(
+        (status = 200, description = "The new url", body = IncomingWebhookOutput),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Webhook not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn regenerate_webhook_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let (webhook, token) = IncomingWebhook::regenerate(id, user.id, &state.pool).await?;
+    Ok(Json(IncomingWebhookOutput::new(&state, webhook, &token)))
+}
+
+/// Remove an incoming webhook
+#[utoipa::path(
+    delete,
+    path = "/api/webhooks/{id}",
+    tag = "integrations",
+    params(("id" = i64, Path, description = "Webhook id")),
+    responses(
+        (status = 204, description = "Removed"),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Webhook not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn delete_webhook_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    IncomingWebhook::delete(id, user.id, &state.pool).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Post a message through an incoming webhook, the token in the url authenticates it
+#[utoipa::path(
+    post,
+    path = "/api/hooks/{token}",
+    tag = "integrations",
+    security(()),
+    params(("token" = String, Path, description = "Secret of the webhook url")),
+    request_body = WebhookPayload,
+    responses(
+        (status = 201, description = "Posted message", body = Message),
+        (status = 404, description = "Webhook not found", body = ErrorOutput),
+        (status = 429, description = "Too many posts", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn incoming_webhook_handler(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    ValidJson(payload): ValidJson<WebhookPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    let webhook = IncomingWebhook::find_by_token(&token, &state.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("webhook".to_string()))?;
+    state
+        .webhook_limiter
+        .acquire(RateLimitKey::Webhook(webhook.id))?;
+
+    let input = payload.into_message();
+    let base_dir = &state.config.server.base_dir;
+    let message = Message::create(
+        &input,
+        webhook.chat_id,
+        webhook.bot_id,
+        base_dir,
+        &state.pool,
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(message)))
+}
+
+/// Subscribe a url to events of the user's chats
+#[utoipa::path(
+    post,
+    path = "/api/outgoing-webhooks",
+    tag = "integrations",
+    request_body = CreateOutgoingWebhook,
+    responses(
+        (status = 201, description = "Created, with the signing secret", body = OutgoingWebhookOutput),
+        (status = 403, description = "Not a member of the chat", body = ErrorOutput),
+        (status = 404, description = "Chat not found", body = ErrorOutput),
+        (status = 422, description = "Invalid fields", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn create_outgoing_webhook_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<CreateOutgoingWebhook>,
+) -> Result<impl IntoResponse, AppError> {
+    let webhook =
+        OutgoingWebhook::create(user.id, &input, &state.config.outbound, &state.pool).await?;
+    let secret = webhook.secret.clone();
+    Ok((
+        StatusCode::CREATED,
+        Json(OutgoingWebhookOutput { webhook, secret }),
+    ))
+}
+
+/// List the outgoing webhooks of the user
+#[utoipa::path(
+    get,
+    path = "/api/outgoing-webhooks",
+    tag = "integrations",
+    responses((status = 200, description = "Webhooks", body = Vec<OutgoingWebhook>))
+)]
+pub(crate) async fn list_outgoing_webhooks_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let webhooks = OutgoingWebhook::list(user.id, &state.pool).await?;
+    Ok(Json(webhooks))
+}
+
+/// Remove an outgoing webhook
+#[utoipa::path(
+    delete,
+    path = "/api/outgoing-webhooks/{id}",
+    tag = "integrations",
+    params(("id" = i64, Path, description = "Webhook id")),
+    responses(
+        (status = 204, description = "Removed"),
+        (status = 404, description = "Webhook not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn delete_outgoing_webhook_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    OutgoingWebhook::delete(user.id, id, &state.pool).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the deliveries of an outgoing webhook, newest first
+#[utoipa::path(
+    get,
+    path = "/api/outgoing-webhooks/{id}/deliveries",
+    tag = "integrations",
+    params(("id" = i64, Path, description = "Webhook id"), ListDeliveries),
+    responses(
+        (status = 200, description = "Deliveries", body = Vec<WebhookDelivery>),
+        (status = 404, description = "Webhook not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn list_deliveries_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(input): Query<ListDeliveries>,
+) -> Result<impl IntoResponse, AppError> {
+    let deliveries = OutgoingWebhook::deliveries(user.id, id, &input, &state.pool).await?;
+    Ok(Json(deliveries))
+}
+
+/// Retry a failed delivery right away
+#[utoipa::path(
+    post,
+    path = "/api/outgoing-webhooks/{id}/deliveries/{delivery_id}/retry",
+    tag = "integrations",
+    params(
+        ("id" = i64, Path, description = "Webhook id"),
+        ("delivery_id" = i64, Path, description = "Delivery id"),
+    ),
+    responses(
+        (status = 202, description = "Queued"),
+        (status = 404, description = "Delivery not found", body = ErrorOutput),
+    )
+)]
+pub(crate) async fn retry_delivery_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path((id, delivery_id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse, AppError> {
+    OutgoingWebhook::retry_delivery(user.id, id, delivery_id, &state.pool).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+impl IncomingWebhookOutput {
+    fn new(state: &AppState, webhook: IncomingWebhook, token: &str) -> Self {
+        let url = format!("{}/api/hooks/{}", state.config.server.public_url, token);
+        Self { webhook, url }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::RateLimitRule, models::CreateUser, AppConfig};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn incoming_webhook_should_be_rate_limited() -> Result<()> {
+        let mut config = AppConfig::load()?;
+        config.rate_limit.webhook = RateLimitRule {
+            burst: 1,
+            per_second: 0.01,
+        };
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        sqlx::query("INSERT INTO chats (name, type, members) VALUES ('ci', 'group', $1)")
+            .bind(vec![user.id])
+            .execute(&state.pool)
+            .await?;
+        let input = CreateIncomingWebhook {
+            name: "GitHub".to_string(),
+        };
+        let (_, token) = IncomingWebhook::create(1, &user, &input, &state.pool).await?;
+
+        let payload = WebhookPayload {
+            text: "build passed".to_string(),
+            ..Default::default()
+        };
+        let ret = incoming_webhook_handler(
+            State(state.clone()),
+            Path(token.clone()),
+            ValidJson(payload.clone()),
+        )
+        .await?
+        .into_response();
+        assert_eq!(ret.status(), StatusCode::CREATED);
+
+        let ret = incoming_webhook_handler(
+            State(state.clone()),
+            Path(token),
+            ValidJson(payload.clone()),
+        )
+        .await;
+        assert!(matches!(ret, Err(AppError::TooManyRequests(_))));
+
+        let ret = incoming_webhook_handler(
+            State(state),
+            Path("bad-token".to_string()),
+            ValidJson(payload),
+        )
+        .await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+        Ok(())
+    }
+}
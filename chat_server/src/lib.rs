@@ -1,27 +1,51 @@
+mod commands;
 mod config;
 mod error;
+mod extractors;
 mod handlers;
+mod mailer;
+mod metrics;
 mod middlewares;
 mod models;
+mod openapi;
+mod reload;
+mod shutdown;
+mod telemetry;
 mod utils;
+mod validation;
+mod workers;
 
+use crate::metrics::metrics_router;
 use anyhow::Context;
+use arc_swap::ArcSwap;
+use chat_core::prometheus;
+use commands::CommandRegistry;
+use config::{AuthConfig, UploadConfig};
 use handlers::*;
-use middlewares::{set_layer, verify_token};
+use mailer::Mailer;
+use middlewares::{set_layer, verify_token, RateLimitLayer, RateLimiter};
+use shutdown::Shutdown;
 use sqlx::PgPool;
-use std::{fmt, ops::Deref, sync::Arc};
+use std::{
+    fmt, future::Future, future::IntoFuture, net::SocketAddr, ops::Deref, sync::Arc, time::Duration,
+};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
 use utils::{DecodingKey, EncodingKey};
 
 pub use error::{AppError, ErrorOutput};
 pub use models::User;
 
 use axum::{
+    extract::DefaultBodyLimit,
     middleware::from_fn_with_state,
-    routing::{get, patch, post},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 
+pub use chat_core::{config_arg, init_logging, shutdown_tracing};
 pub use config::AppConfig;
+pub use shutdown::shutdown_signal;
 
 #[derive(Debug, Clone)]
 pub(crate) struct AppState {
@@ -30,14 +54,91 @@ pub(crate) struct AppState {
 
 #[allow(unused)]
 pub(crate) struct AppStateInner {
+    /// the config as loaded at startup, parts that can be reloaded are held below
     pub(crate) config: AppConfig,
+    pub(crate) keys: ArcSwap<Keys>,
+    pub(crate) upload: ArcSwap<UploadConfig>,
+    pub(crate) pool: PgPool,
+    pub(crate) mailer: Arc<dyn Mailer>,
+    pub(crate) public_limiter: Arc<RateLimiter>,
+    pub(crate) api_limiter: Arc<RateLimiter>,
+    pub(crate) api_ip_limiter: Arc<RateLimiter>,
+    pub(crate) webhook_limiter: RateLimiter,
+    pub(crate) commands: CommandRegistry,
+    pub(crate) shutdown: Shutdown,
+}
+
+/// Keys tokens are signed and verified with
+pub(crate) struct Keys {
     pub(crate) dk: DecodingKey,
     pub(crate) ek: EncodingKey,
-    pub(crate) pool: PgPool,
 }
 
+/// The app without its background workers (webhook delivery, scheduled messages, config reload),
+/// `serve` starts them
 pub async fn get_router(config: AppConfig) -> Result<Router, AppError> {
     let state = AppState::try_new(config).await?;
+    Ok(router(state))
+}
+
+/// Serve until `signal` resolves, then stop accepting connections, give in-flight requests
+/// `server.shutdown_timeout_secs` to finish and close the db pool
+pub async fn serve(
+    listener: TcpListener,
+    config: AppConfig,
+    signal: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), AppError> {
+    let state = AppState::try_new(config).await?;
+    if let Some(port) = state.config.server.metrics_port {
+        let addr = format!("0.0.0.0:{}", port);
+        let metrics = TcpListener::bind(&addr).await?;
+        info!("serving metrics on: {}", addr);
+        let app = metrics_router(state.clone());
+        tokio::spawn(async move { axum::serve(metrics, app).await });
+    }
+    workers::spawn(&state);
+    serve_with_state(listener, state, signal).await
+}
+
+async fn serve_with_state(
+    listener: TcpListener,
+    state: AppState,
+    signal: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), AppError> {
+    let timeout = Duration::from_secs(state.config.server.shutdown_timeout_secs);
+    let graceful = {
+        let state = state.clone();
+        async move {
+            signal.await;
+            info!("shutting down, draining in-flight requests");
+            state.shutdown.trigger();
+        }
+    };
+    let app = router(state.clone());
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(graceful);
+    let deadline = async {
+        state.shutdown.wait().await;
+        tokio::time::sleep(timeout).await;
+    };
+
+    tokio::select! {
+        ret = server.into_future() => ret?,
+        _ = deadline => warn!(
+            "requests still running after {}s, closing them",
+            timeout.as_secs()
+        ),
+    }
+    state.pool.close().await;
+    info!("shutdown complete");
+    Ok(())
+}
+
+fn router(state: AppState) -> Router {
+    let trust_proxy = state.config.server.trust_proxy;
 
     let api = Router::new()
         .route("/chat", get(list_chat_handler).post(create_chat_handler))
@@ -48,17 +149,103 @@ pub async fn get_router(config: AppConfig) -> Result<Router, AppError> {
                 .post(send_message_handler),
         )
         .route("/chat/:id/messages", get(list_message_handler))
+        .route(
+            "/chat/:id/webhooks",
+            get(list_webhooks_handler).post(create_webhook_handler),
+        )
+        .route("/chat/:id/scheduled", post(schedule_message_handler))
+        .route("/scheduled", get(list_scheduled_messages_handler))
+        .route("/scheduled/:id", delete(cancel_scheduled_message_handler))
+        .route("/chat/:id/pins", get(list_pins_handler))
+        .route(
+            "/chat/:id/pins/:message_id",
+            post(pin_message_handler).delete(unpin_message_handler),
+        )
+        .route(
+            "/chat/:id/commands",
+            get(list_commands_handler).post(create_command_handler),
+        )
+        .route("/commands/:id", delete(delete_command_handler))
+        .route("/webhooks/:id", delete(delete_webhook_handler))
+        .route("/webhooks/:id/regenerate", post(regenerate_webhook_handler))
+        .route(
+            "/outgoing-webhooks",
+            get(list_outgoing_webhooks_handler).post(create_outgoing_webhook_handler),
+        )
+        .route(
+            "/outgoing-webhooks/:id",
+            delete(delete_outgoing_webhook_handler),
+        )
+        .route(
+            "/outgoing-webhooks/:id/deliveries",
+            get(list_deliveries_handler),
+        )
+        .route(
+            "/outgoing-webhooks/:id/deliveries/:delivery_id/retry",
+            post(retry_delivery_handler),
+        )
+        .route("/search", get(search_messages_handler))
+        .route("/mentions", get(list_mentions_handler))
+        .route("/bookmarks", get(list_bookmarks_handler))
+        .route(
+            "/bookmarks/:message_id",
+            post(create_bookmark_handler).delete(delete_bookmark_handler),
+        )
+        .route("/users", get(list_users_handler))
+        .route("/users/me", patch(update_profile_handler))
+        .route("/users/me/password", put(change_password_handler))
+        .route(
+            "/users/me/2fa",
+            post(enroll_totp_handler).delete(disable_totp_handler),
+        )
+        .route("/users/me/2fa/confirm", post(confirm_totp_handler))
+        .route("/users/:id", get(get_user_handler))
+        .route("/bots", get(list_bots_handler).post(create_bot_handler))
+        .route(
+            "/tokens",
+            get(list_tokens_handler).post(create_token_handler),
+        )
+        .route("/tokens/:id", delete(revoke_token_handler))
+        // limited per file in the handler, the limits can change at runtime
+        .route(
+            "/upload",
+            post(upload_handler).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/files/*path", get(file_handler))
+        .layer(RateLimitLayer::new(state.api_limiter.clone(), trust_proxy))
         .layer(from_fn_with_state(state.clone(), verify_token))
-        // routes doesn't need token verification
+        // outside verify_token, so requests with a bad token are limited by ip
+        .layer(RateLimitLayer::new(
+            state.api_ip_limiter.clone(),
+            trust_proxy,
+        ));
+
+    // routes doesn't need token verification
+    let public = Router::new()
         .route("/signin", post(signin_handler))
-        .route("/signup", post(signup_handler));
+        .route("/signin/2fa", post(signin_mfa_handler))
+        .route("/signup", post(signup_handler))
+        .route("/forgot-password", post(forgot_password_handler))
+        .route("/reset-password", post(reset_password_handler))
+        .route("/verify-email", get(verify_email_handler))
+        .route("/resend-verification", post(resend_verification_handler))
+        .layer(RateLimitLayer::new(
+            state.public_limiter.clone(),
+            trust_proxy,
+        ));
+
+    // the secret in the path authenticates the caller, rate limited per webhook in the handler
+    let hooks = Router::new().route("/hooks/:token", post(incoming_webhook_handler));
 
     let app = Router::new()
         .route("/", get(index_handler))
-        .nest("/api", api)
+        .route("/healthz", get(health_handler))
+        .route("/readyz", get(ready_handler))
+        .nest("/api", api.merge(public).merge(hooks))
+        .merge(openapi::docs())
         .with_state(state);
 
-    Ok(set_layer(app))
+    set_layer(app)
 }
 
 // 当我调用 state.config => state.inner.config
@@ -72,20 +259,65 @@ impl Deref for AppState {
 
 impl AppState {
     pub async fn try_new(config: AppConfig) -> Result<Self, AppError> {
-        let dk = DecodingKey::load(&config.auth.pk).context("load pk failed")?;
-        let ek = EncodingKey::load(&config.auth.sk).context("load sk failed")?;
         let pool = PgPool::connect(&config.server.db_url)
             .await
             .context("connect to db failed")?;
+        Self::with_pool(config, pool)
+    }
+
+    fn with_pool(config: AppConfig, pool: PgPool) -> Result<Self, AppError> {
+        // install the recorder before anything records
+        prometheus();
+        let keys = Keys::load(&config.auth)?;
+        let mailer = mailer::new_mailer(&config.mail)?;
+        let rate_limit = &config.rate_limit;
         Ok(Self {
             inner: Arc::new(AppStateInner {
-                config,
-                ek,
-                dk,
+                keys: ArcSwap::from_pointee(keys),
+                upload: ArcSwap::from_pointee(config.upload.clone()),
                 pool,
+                mailer,
+                public_limiter: Arc::new(RateLimiter::new(rate_limit.public)),
+                api_limiter: Arc::new(RateLimiter::new(rate_limit.api)),
+                api_ip_limiter: Arc::new(RateLimiter::new(rate_limit.api_ip)),
+                webhook_limiter: RateLimiter::new(rate_limit.webhook),
+                commands: CommandRegistry::new(&config.outbound),
+                shutdown: Shutdown::new(),
+                config,
             }),
         })
     }
+
+    pub(crate) fn keys(&self) -> Arc<Keys> {
+        self.keys.load_full()
+    }
+}
+
+impl Keys {
+    pub(crate) fn load(auth: &AuthConfig) -> Result<Self, AppError> {
+        let dk = DecodingKey::load(&auth.pk).context("load pk failed")?;
+        let ek = EncodingKey::load(&auth.sk).context("load sk failed")?;
+        Ok(Self { dk, ek })
+    }
+
+    /// Sign and verify a token, fails if the keys aren't a pair
+    pub(crate) fn check(&self) -> Result<(), AppError> {
+        let probe = User {
+            id: 0,
+            fullname: "probe".to_string(),
+            email: "probe@localhost".to_string(),
+            password_hash: None,
+            avatar_url: None,
+            status_text: None,
+            timezone: None,
+            email_verified_at: None,
+            is_bot: false,
+            created_at: chrono::Utc::now(),
+        };
+        let token = self.ek.sign(probe)?;
+        self.dk.verify(&token)?;
+        Ok(())
+    }
 }
 
 impl fmt::Debug for AppStateInner {
@@ -102,8 +334,6 @@ impl AppState {
         config: AppConfig,
     ) -> Result<(sqlx_db_tester::TestPg, Self), AppError> {
         use sqlx_db_tester::TestPg;
-        let dk = DecodingKey::load(&config.auth.pk).context("load pk failed")?;
-        let ek = EncodingKey::load(&config.auth.sk).context("load sk failed")?;
         let post = config.server.db_url.rfind('/').expect("invalid db_url");
         let server_url = &config.server.db_url[..post];
         let tdb = TestPg::new(
@@ -111,14 +341,63 @@ impl AppState {
             std::path::Path::new("../migrations"),
         );
         let pool = tdb.get_pool().await;
-        let state = Self {
-            inner: Arc::new(AppStateInner {
-                config,
-                ek,
-                dk,
-                pool,
-            }),
-        };
+        let state = Self::with_pool(config, pool)?;
         Ok((tdb, state))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RateLimitRule;
+    use anyhow::Result;
+    use axum::{body::Body, extract::Request, http::StatusCode};
+    use tokio::sync::oneshot;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn serve_should_shut_down_gracefully() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        let (tx, rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve_with_state(listener, state.clone(), async {
+            let _ = rx.await;
+        }));
+
+        let res = reqwest::get(&url).await?;
+        assert!(res.status().is_success());
+
+        tx.send(()).expect("server should be running");
+        server.await??;
+        assert!(state.pool.is_closed());
+        // background tasks see the shutdown too
+        tokio::time::timeout(Duration::from_secs(1), state.shutdown.wait()).await?;
+        assert!(reqwest::get(&url).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn requests_with_bad_tokens_should_be_rate_limited() -> Result<()> {
+        let mut config = AppConfig::load()?;
+        config.rate_limit.api_ip = RateLimitRule {
+            burst: 1,
+            per_second: 0.01,
+        };
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let app = router(state);
+        let request = || {
+            Request::builder()
+                .uri("/api/chat")
+                .header("Authorization", "Bearer bad-token")
+                .body(Body::empty())
+        };
+
+        let res = app.clone().oneshot(request()?).await?;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let res = app.oneshot(request()?).await?;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{
+    config::{MailConfig, MailTransport},
+    AppError,
+};
+use async_trait::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, sync::Arc};
+use tokio::fs;
+use tracing::info;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync + 'static {
+    async fn send(&self, mail: Mail) -> Result<(), AppError>;
+}
+
+/// Deliver mails through an SMTP relay
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+/// Write mails as json files into a directory, for local development and tests
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+pub fn new_mailer(config: &MailConfig) -> Result<Arc<dyn Mailer>, AppError> {
+    let mailer: Arc<dyn Mailer> = match &config.transport {
+        MailTransport::Smtp {
+            host,
+            port,
+            username,
+            password,
+        } => Arc::new(SmtpMailer::try_new(
+            &config.from,
+            host,
+            *port,
+            username.as_deref().zip(password.as_deref()),
+        )?),
+        MailTransport::File { dir } => Arc::new(FileMailer::new(dir.clone())),
+    };
+    Ok(mailer)
+}
+
+impl SmtpMailer {
+    pub fn try_new(
+        from: &str,
+        host: &str,
+        port: Option<u16>,
+        credentials: Option<(&str, &str)>,
+    ) -> Result<Self, AppError> {
+        let from = from
+            .parse()
+            .map_err(|e| AppError::MailError(format!("invalid from address: {}", e)))?;
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|e| AppError::MailError(e.to_string()))?;
+        if let Some(port) = port {
+            builder = builder.port(port);
+        }
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username.into(), password.into()));
+        }
+        Ok(Self {
+            from,
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: Mail) -> Result<(), AppError> {
+        let to = mail
+            .to
+            .parse()
+            .map_err(|e| AppError::MailError(format!("invalid to address: {}", e)))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(mail.subject)
+            .body(mail.body)
+            .map_err(|e| AppError::MailError(e.to_string()))?;
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| AppError::MailError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), AppError> {
+        fs::create_dir_all(&self.dir).await?;
+        let path = self.dir.join(format!("{}.json", uuid::Uuid::now_v7()));
+        let data = serde_json::to_vec_pretty(&mail).expect("mail should serialize");
+        fs::write(&path, data).await?;
+        info!(
+            "Mail `{}` to {} written to {:?}",
+            mail.subject, mail.to, path
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn file_mailer_should_write_mail() -> Result<()> {
+        let dir = std::env::temp_dir().join(uuid::Uuid::now_v7().to_string());
+        let mailer = FileMailer::new(&dir);
+        let mail = Mail {
+            to: "tchen@acme.org".to_string(),
+            subject: "hello".to_string(),
+            body: "world".to_string(),
+        };
+        mailer.send(mail.clone()).await?;
+
+        let mut entries = std::fs::read_dir(&dir)?;
+        let entry = entries.next().expect("mail should be written")?;
+        let ret: Mail = serde_json::from_slice(&std::fs::read(entry.path())?)?;
+        assert_eq!(ret, mail);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
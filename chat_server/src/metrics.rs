@@ -0,0 +1,49 @@
+use crate::AppState;
+use axum::{extract::State, response::IntoResponse, routing::get, Router};
+use chat_core::render_metrics;
+use metrics::gauge;
+
+/// /metrics, served on `server.metrics_port` only, so it isn't reachable where the api is
+pub(crate) fn metrics_router(state: AppState) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(state)
+}
+
+// pool stats are sampled on each scrape
+async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    gauge!("db_pool_connections").set(state.pool.size() as f64);
+    gauge!("db_pool_idle_connections").set(state.pool.num_idle() as f64);
+    gauge!("db_pool_max_connections").set(state.pool.options().get_max_connections() as f64);
+    render_metrics()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{router, AppConfig};
+    use anyhow::Result;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn metrics_should_only_be_served_by_the_metrics_router() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+
+        let req = Request::get("/metrics").body(Body::empty())?;
+        let res = router(state.clone()).oneshot(req).await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let req = Request::get("/metrics").body(Body::empty())?;
+        let res = metrics_router(state).oneshot(req).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = res.into_body().collect().await?.to_bytes();
+        assert!(String::from_utf8(body.to_vec())?.contains("db_pool_max_connections"));
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use axum::{
     extract::{FromRequestParts, Request, State},
-    http::StatusCode,
+    http::{Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
@@ -10,7 +10,11 @@ use axum_extra::{
 };
 use tracing::warn;
 
-use crate::AppState;
+use crate::{
+    config::EmailVerification,
+    models::{ApiToken, ApiTokenAuth},
+    AppError, AppState, User,
+};
 
 pub async fn verify_token(State(state): State<AppState>, req: Request, next: Next) -> Response {
     let (mut parts, body) = req.into_parts();
@@ -18,16 +22,27 @@ pub async fn verify_token(State(state): State<AppState>, req: Request, next: Nex
         match TypedHeader::<Authorization<Bearer>>::from_request_parts(&mut parts, &state).await {
             Ok(TypedHeader(Authorization(bearer))) => {
                 let token = bearer.token();
-                match state.dk.verify(token) {
-                    Ok(user) => {
+                let ret = if ApiToken::is_api_token(token) {
+                    verify_api_token(&state, token, &parts.method).await
+                } else {
+                    state.keys().dk.verify(token).map(|user| (user, None))
+                };
+                match ret {
+                    Ok((user, auth)) => {
+                        if let Err(e) = check_email_verified(&state, &user, &parts.method).await {
+                            warn!("reject user {}: {}", user.id, e);
+                            return e.into_response();
+                        }
                         let mut req = Request::from_parts(parts, body);
                         req.extensions_mut().insert(user);
+                        if let Some(auth) = auth {
+                            req.extensions_mut().insert(auth);
+                        }
                         req
                     }
                     Err(e) => {
-                        let msg = format!("verify token failed: {}", e);
-                        warn!(msg);
-                        return (StatusCode::FORBIDDEN, msg).into_response();
+                        warn!("{}", e);
+                        return e.into_response();
                     }
                 }
             }
@@ -40,3 +55,151 @@ pub async fn verify_token(State(state): State<AppState>, req: Request, next: Nex
 
     next.run(req).await
 }
+
+async fn verify_api_token(
+    state: &AppState,
+    token: &str,
+    method: &Method,
+) -> Result<(User, Option<ApiTokenAuth>), AppError> {
+    let Some((user, auth)) = ApiToken::verify(token, &state.pool).await? else {
+        return Err(AppError::PermissionDenied(
+            "invalid or expired api token".to_string(),
+        ));
+    };
+    if !auth.allows(method) {
+        return Err(AppError::PermissionDenied(format!(
+            "api token {} lacks the write scope",
+            auth.token_id
+        )));
+    }
+    Ok((user, Some(auth)))
+}
+
+async fn check_email_verified(
+    state: &AppState,
+    user: &User,
+    method: &Method,
+) -> Result<(), AppError> {
+    match state.config.auth.email_verification {
+        EmailVerification::Optional => return Ok(()),
+        EmailVerification::ReadOnly if method == Method::GET || method == Method::HEAD => {
+            return Ok(())
+        }
+        _ => {}
+    }
+    // the token may have been issued before the email got verified
+    if user.email_verified_at.is_some() || User::is_email_verified(user.id, &state.pool).await? {
+        return Ok(());
+    }
+    Err(AppError::PermissionDenied(
+        "email is not verified".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{CreateApiToken, CreateUser},
+        AppConfig,
+    };
+    use anyhow::Result;
+    use axum::{body::Body, middleware::from_fn_with_state, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn handler(_req: Request) -> impl IntoResponse {
+        (StatusCode::OK, "ok")
+    }
+
+    fn request(method: Method, token: &str) -> Result<Request> {
+        let req = Request::builder()
+            .method(method)
+            .uri("/")
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::empty())?;
+        Ok(req)
+    }
+
+    #[tokio::test]
+    async fn verify_token_should_work() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let user = User::new(1, "Tyr Chen", "tchen@acme.org");
+        let token = state.keys().ek.sign(user)?;
+        let app = Router::new()
+            .route("/", get(handler))
+            .layer(from_fn_with_state(state.clone(), verify_token));
+
+        let res = app.clone().oneshot(request(Method::GET, &token)?).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = app
+            .clone()
+            .oneshot(request(Method::GET, "bad-token")?)
+            .await?;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let req = Request::builder().uri("/").body(Body::empty())?;
+        let res = app.oneshot(req).await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unverified_user_should_be_read_only() -> Result<()> {
+        let mut config = AppConfig::load()?;
+        config.auth.email_verification = EmailVerification::ReadOnly;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        let token = state.keys().ek.sign(user.clone())?;
+        let app = Router::new()
+            .route("/", get(handler).post(handler))
+            .layer(from_fn_with_state(state.clone(), verify_token));
+
+        let res = app.clone().oneshot(request(Method::GET, &token)?).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = app.clone().oneshot(request(Method::POST, &token)?).await?;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        // tokens issued before verification work once the email is verified
+        let code = user.create_email_verification(&state.pool).await?;
+        User::verify_email(&code, &state.pool).await?;
+        let res = app.oneshot(request(Method::POST, &token)?).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn api_token_should_respect_scopes() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        let input = CreateApiToken {
+            name: "ci".to_string(),
+            scopes: vec!["read".to_string()],
+            ..Default::default()
+        };
+        let created = ApiToken::create(&user, &input, &state.pool).await?;
+        let app = Router::new()
+            .route("/", get(handler).post(handler))
+            .layer(from_fn_with_state(state.clone(), verify_token));
+
+        let res = app
+            .clone()
+            .oneshot(request(Method::GET, &created.token)?)
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = app
+            .clone()
+            .oneshot(request(Method::POST, &created.token)?)
+            .await?;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        ApiToken::revoke(user.id, created.api_token.id, &state.pool).await?;
+        let res = app.oneshot(request(Method::GET, &created.token)?).await?;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        Ok(())
+    }
+}
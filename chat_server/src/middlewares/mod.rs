@@ -1,28 +1,57 @@
 mod auth;
+mod rate_limit;
 mod request_id;
 mod server_time;
 
 use self::{request_id::set_request_id, server_time::ServerTimeLayer};
-use axum::{middleware::from_fn, Router};
+use crate::telemetry::{parent_context, trace_id};
+use axum::{
+    extract::Request,
+    http::{
+        header::{AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE},
+        Uri,
+    },
+    middleware::from_fn,
+    Router,
+};
+use chat_core::track_metrics;
 use tower::ServiceBuilder;
 use tower_http::{
     compression::CompressionLayer,
-    trace::{DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, TraceLayer},
+    sensitive_headers::SetSensitiveHeadersLayer,
+    trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer},
     LatencyUnit,
 };
-use tracing::Level;
+use tracing::{field::Empty, info_span, Level, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 pub use auth::verify_token;
+pub use rate_limit::RateLimitLayer;
+pub(crate) use rate_limit::{RateLimitKey, RateLimiter};
+pub(crate) use request_id::RequestContext;
 
 const REQUEST_ID_HEADER: &str = "x-request-id";
 const SERVER_TIME_HEADER: &str = "x-server-time";
+// query params carrying secrets, e.g. the link of the verification email
+const SENSITIVE_PARAMS: &[&str] = &["token", "access_token"];
+// paths whose next segment is a secret, e.g. the token of an incoming webhook
+const SENSITIVE_PATHS: &[&str] = &["/api/hooks/"];
 
 pub fn set_layer(app: Router) -> Router {
     app.layer(
         ServiceBuilder::new()
+            // logged as "Sensitive" by the request span
+            .layer(SetSensitiveHeadersLayer::new([
+                AUTHORIZATION,
+                PROXY_AUTHORIZATION,
+                COOKIE,
+                SET_COOKIE,
+            ]))
+            // outside the trace layer, so the request span knows the id
+            .layer(from_fn(set_request_id))
             .layer(
                 TraceLayer::new_for_http()
-                    .make_span_with(DefaultMakeSpan::new().include_headers(true))
+                    .make_span_with(make_span)
                     .on_request(DefaultOnRequest::new().level(Level::INFO))
                     .on_response(
                         DefaultOnResponse::new()
@@ -31,7 +60,92 @@ pub fn set_layer(app: Router) -> Router {
                     ),
             )
             .layer(CompressionLayer::new().gzip(true).br(true).deflate(true))
-            .layer(from_fn(set_request_id))
+            .layer(from_fn(track_metrics))
             .layer(ServerTimeLayer),
     )
 }
+
+// continues the caller's trace, logs of the request carry both its id and the trace id
+fn make_span(req: &Request) -> Span {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let span = info_span!(
+        "request",
+        method = %req.method(),
+        uri = %redact_uri(req.uri()),
+        version = ?req.version(),
+        headers = ?req.headers(),
+        request_id,
+        trace_id = Empty,
+    );
+    span.set_parent(parent_context(req.headers()));
+    if let Some(id) = trace_id(&span) {
+        span.record("trace_id", id);
+    }
+    span
+}
+
+fn redact_uri(uri: &Uri) -> String {
+    let mut path = uri.path().to_string();
+    for prefix in SENSITIVE_PATHS {
+        if let Some(rest) = uri.path().strip_prefix(prefix) {
+            let tail = rest.find('/').map_or("", |i| &rest[i..]);
+            path = format!("{}[redacted]{}", prefix, tail);
+        }
+    }
+    let Some(query) = uri.query() else {
+        return path;
+    };
+    let params: Vec<_> = query
+        .split('&')
+        .map(|param| match param.split_once('=') {
+            Some((name, _)) if SENSITIVE_PARAMS.contains(&name) => format!("{}=[redacted]", name),
+            _ => param.to_string(),
+        })
+        .collect();
+    format!("{}?{}", path, params.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, routing::get};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn set_layer_should_mark_credentials_sensitive() -> anyhow::Result<()> {
+        let app = set_layer(Router::new().route(
+            "/",
+            get(|req: Request| async move {
+                assert!(req.headers()[AUTHORIZATION].is_sensitive());
+                assert!(!req.headers()["x-client"].is_sensitive());
+                StatusCode::OK
+            }),
+        ));
+        let req = Request::get("/")
+            .header(AUTHORIZATION, "Bearer secret")
+            .header("x-client", "test")
+            .body(Body::empty())?;
+        let res = app.oneshot(req).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        Ok(())
+    }
+
+    #[test]
+    fn redact_uri_should_hide_tokens() {
+        let uri: Uri = "/api/verify-email?token=abc&next=%2Fchats".parse().unwrap();
+        assert_eq!(
+            redact_uri(&uri),
+            "/api/verify-email?token=[redacted]&next=%2Fchats"
+        );
+        let uri: Uri = "/api/chats/1/messages?limit=10".parse().unwrap();
+        assert_eq!(redact_uri(&uri), "/api/chats/1/messages?limit=10");
+        let uri: Uri = "/api/hooks/s3cr3t".parse().unwrap();
+        assert_eq!(redact_uri(&uri), "/api/hooks/[redacted]");
+        let uri: Uri = "/api/hooks/s3cr3t/extra?x=1".parse().unwrap();
+        assert_eq!(redact_uri(&uri), "/api/hooks/[redacted]/extra?x=1");
+    }
+}
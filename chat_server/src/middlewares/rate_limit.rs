@@ -0,0 +1,299 @@
+use crate::{config::RateLimitRule, extractors::ClientIp, AppError, User};
+use axum::{
+    extract::Request,
+    http::{HeaderMap, HeaderName},
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::IpAddr,
+    pin::Pin,
+    sync::{Arc, Mutex, RwLock},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tower::{Layer, Service};
+
+const RATE_LIMIT_LIMIT_HEADER: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATE_LIMIT_REMAINING_HEADER: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATE_LIMIT_RESET_HEADER: HeaderName = HeaderName::from_static("ratelimit-reset");
+// how often buckets that have filled up again are dropped
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Token bucket rate limiting, keyed by the authenticated user when `verify_token` ran before,
+/// otherwise by the client ip
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+    trust_proxy: bool,
+}
+
+#[derive(Clone)]
+pub struct RateLimitMiddleware<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+    trust_proxy: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum RateLimitKey {
+    User(i64),
+    Ip(IpAddr),
+    Webhook(i64),
+}
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    /// replaced when the config is reloaded, buckets keep their tokens
+    rule: RwLock<RateLimitRule>,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    map: HashMap<RateLimitKey, Bucket>,
+    swept_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Outcome of taking a token, used to fill the RateLimit headers
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Decision {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    /// seconds until the bucket is full again
+    reset: u64,
+    /// seconds until the next token is available, only meaningful when not allowed
+    retry_after: u64,
+}
+
+impl RateLimitLayer {
+    /// The limiter is shared with the app state, so its rule can be changed at runtime
+    pub(crate) fn new(limiter: Arc<RateLimiter>, trust_proxy: bool) -> Self {
+        Self {
+            limiter,
+            trust_proxy,
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitMiddleware {
+            inner,
+            limiter: self.limiter.clone(),
+            trust_proxy: self.trust_proxy,
+        }
+    }
+}
+
+impl<S> Service<Request> for RateLimitMiddleware<S>
+where
+    S: Service<Request, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let key = match request.extensions().get::<User>() {
+            Some(user) => RateLimitKey::User(user.id),
+            None => {
+                let ClientIp(ip) =
+                    ClientIp::extract(request.headers(), request.extensions(), self.trust_proxy);
+                RateLimitKey::Ip(ip)
+            }
+        };
+        let decision = self.limiter.check(key, Instant::now());
+        if !decision.allowed {
+            let mut res = AppError::TooManyRequests(decision.retry_after).into_response();
+            decision.set_headers(res.headers_mut());
+            return Box::pin(async move { Ok(res) });
+        }
+
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let mut res: Response = future.await?;
+            decision.set_headers(res.headers_mut());
+            Ok(res)
+        })
+    }
+}
+
+impl RateLimiter {
+    pub(crate) fn new(rule: RateLimitRule) -> Self {
+        Self {
+            rule: RwLock::new(rule),
+            buckets: Mutex::new(Buckets {
+                map: HashMap::new(),
+                swept_at: Instant::now(),
+            }),
+        }
+    }
+
+    pub(crate) fn set_rule(&self, rule: RateLimitRule) {
+        *self.rule.write().expect("rate limit rule poisoned") = rule;
+    }
+
+    /// Take a token for a key only a handler knows, e.g. the webhook behind a secret url
+    pub(crate) fn acquire(&self, key: RateLimitKey) -> Result<(), AppError> {
+        let decision = self.check(key, Instant::now());
+        if !decision.allowed {
+            return Err(AppError::TooManyRequests(decision.retry_after));
+        }
+        Ok(())
+    }
+
+    fn check(&self, key: RateLimitKey, now: Instant) -> Decision {
+        let rule = *self.rule.read().expect("rate limit rule poisoned");
+        let capacity = rule.burst as f64;
+        let rate = rule.per_second;
+        let mut buckets = self.buckets.lock().expect("rate limit buckets poisoned");
+        if now.saturating_duration_since(buckets.swept_at) >= SWEEP_INTERVAL {
+            // a full bucket is the same as a missing one
+            buckets
+                .map
+                .retain(|_, bucket| bucket.refill(now, capacity, rate).tokens < capacity);
+            buckets.swept_at = now;
+        }
+
+        let bucket = buckets.map.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        *bucket = bucket.refill(now, capacity, rate);
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        Decision {
+            allowed,
+            limit: rule.burst,
+            remaining: bucket.tokens.floor() as u32,
+            reset: secs_until(capacity - bucket.tokens, rate),
+            retry_after: secs_until(1.0 - bucket.tokens, rate).max(1),
+        }
+    }
+}
+
+impl Bucket {
+    fn refill(&self, now: Instant, capacity: f64, rate: f64) -> Self {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        Self {
+            tokens: (self.tokens + elapsed * rate).min(capacity),
+            updated_at: now,
+        }
+    }
+}
+
+impl Decision {
+    fn set_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(RATE_LIMIT_LIMIT_HEADER, self.limit.into());
+        headers.insert(RATE_LIMIT_REMAINING_HEADER, self.remaining.into());
+        headers.insert(RATE_LIMIT_RESET_HEADER, self.reset.into());
+    }
+}
+
+fn secs_until(missing: f64, rate: f64) -> u64 {
+    if missing <= 0.0 {
+        0
+    } else if rate <= 0.0 {
+        u64::MAX
+    } else {
+        (missing / rate).ceil() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use axum::{body::Body, http::StatusCode, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn token_bucket_should_refill() {
+        let limiter = RateLimiter::new(RateLimitRule {
+            burst: 2,
+            per_second: 1.0,
+        });
+        let key = RateLimitKey::User(1);
+        let now = Instant::now();
+        assert!(limiter.check(key, now).allowed);
+        let decision = limiter.check(key, now);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.reset, 2);
+
+        let decision = limiter.check(key, now);
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, 1);
+
+        // other keys have their own bucket
+        assert!(limiter.check(RateLimitKey::User(2), now).allowed);
+        assert!(limiter.check(key, now + Duration::from_secs(1)).allowed);
+    }
+
+    #[test]
+    fn idle_buckets_should_be_swept() {
+        let limiter = RateLimiter::new(RateLimitRule {
+            burst: 2,
+            per_second: 0.1,
+        });
+        let now = Instant::now();
+        limiter.check(RateLimitKey::User(1), now);
+        limiter.check(
+            RateLimitKey::User(2),
+            now + SWEEP_INTERVAL - Duration::from_secs(1),
+        );
+        assert_eq!(limiter.buckets.lock().unwrap().map.len(), 2);
+
+        // user 1 has been full for a while, user 2 is still refilling
+        limiter.check(RateLimitKey::User(3), now + SWEEP_INTERVAL);
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.map.len(), 2);
+        assert!(!buckets.map.contains_key(&RateLimitKey::User(1)));
+    }
+
+    #[tokio::test]
+    async fn rate_limit_layer_should_return_429() -> Result<()> {
+        let rule = RateLimitRule {
+            burst: 1,
+            per_second: 0.01,
+        };
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(RateLimitLayer::new(Arc::new(RateLimiter::new(rule)), false));
+
+        let res = app
+            .clone()
+            .oneshot(Request::builder().uri("/").body(Body::empty())?)
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[RATE_LIMIT_REMAINING_HEADER], "0");
+
+        let res = app
+            .oneshot(Request::builder().uri("/").body(Body::empty())?)
+            .await?;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()["retry-after"], "100");
+        assert_eq!(res.headers()[RATE_LIMIT_LIMIT_HEADER], "1");
+        Ok(())
+    }
+}
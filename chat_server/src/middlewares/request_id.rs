@@ -1,7 +1,32 @@
 use super::REQUEST_ID_HEADER;
-use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use crate::error::PROBLEM_JSON;
+use axum::{
+    extract::Request,
+    http::{header::ACCEPT, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
 use tracing::warn;
 
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+/// What error responses need to know about the request they answer
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RequestContext {
+    pub(crate) request_id: Option<String>,
+    /// the client asked for RFC 7807 problem details
+    pub(crate) problem_json: bool,
+}
+
+impl RequestContext {
+    /// The context of the request being handled, None outside of a request
+    pub(crate) fn current() -> Option<Self> {
+        REQUEST_CONTEXT.try_with(|ctx| ctx.clone()).ok()
+    }
+}
+
 pub async fn set_request_id(mut req: Request, next: Next) -> Response {
     // if x-request-id exists, do nothing, otherwise generate a new one
 
@@ -22,7 +47,18 @@ pub async fn set_request_id(mut req: Request, next: Next) -> Response {
         }
     };
 
-    let mut res = next.run(req).await;
+    let ctx = RequestContext {
+        request_id: id
+            .as_ref()
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string()),
+        problem_json: req
+            .headers()
+            .get(ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains(PROBLEM_JSON)),
+    };
+    let mut res = REQUEST_CONTEXT.scope(ctx, next.run(req)).await;
 
     let Some(id) = id else {
         return res;
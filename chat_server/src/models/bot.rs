@@ -0,0 +1,69 @@
+use crate::{
+    validation::{Validate, Validator},
+    AppError, User,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateBot {
+    pub fullname: String,
+}
+
+impl User {
+    /// Create a bot account owned by a user, bots can't sign in and only act through api tokens
+    pub async fn create_bot(
+        owner_id: i64,
+        input: &CreateBot,
+        conn: &mut PgConnection,
+    ) -> Result<Self, AppError> {
+        input.check()?;
+        let fullname = input.fullname.trim();
+        // bots need a unique email but never receive mail
+        let email = format!("bot-{}@bots.invalid", uuid::Uuid::now_v7().simple());
+        let bot = sqlx::query_as(
+            r#"
+            INSERT INTO users (email, fullname, password_hash, is_bot, bot_owner_id, email_verified_at)
+            VALUES ($1, $2, '', true, $3, now())
+            RETURNING id, fullname, email, email_verified_at, is_bot, created_at
+            "#,
+        )
+        .bind(email)
+        .bind(fullname)
+        .bind(owner_id)
+        .fetch_one(conn)
+        .await?;
+        Ok(bot)
+    }
+
+    /// List bots owned by a user
+    pub async fn list_bots(owner_id: i64, pool: &PgPool) -> Result<Vec<Self>, AppError> {
+        let bots = sqlx::query_as(
+            r#"
+            SELECT id, fullname, email, email_verified_at, is_bot, created_at
+            FROM users WHERE bot_owner_id = $1 AND is_bot
+            ORDER BY id
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(bots)
+    }
+}
+
+impl Validate for CreateBot {
+    fn rules(&self, v: &mut Validator) {
+        v.field("fullname", &self.fullname).required().max_chars(64);
+    }
+}
+
+#[cfg(test)]
+impl CreateBot {
+    pub fn new(fullname: &str) -> Self {
+        Self {
+            fullname: fullname.to_string(),
+        }
+    }
+}
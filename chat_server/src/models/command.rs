@@ -0,0 +1,162 @@
+use crate::{
+    config::OutboundConfig,
+    models::{Chat, CreateBot},
+    utils::{check_url, generate_token},
+    validation::{Validate, Validator},
+    AppError, User,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use utoipa::ToSchema;
+
+/// An external slash command registered for a chat
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct SlashCommand {
+    pub id: i64,
+    pub chat_id: i64,
+    /// the bot public responses are posted as
+    pub bot_id: i64,
+    pub creator_id: i64,
+    pub name: String,
+    pub url: String,
+    #[serde(skip)]
+    pub secret: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct CreateSlashCommand {
+    /// without the leading slash
+    pub name: String,
+    pub url: String,
+    pub description: Option<String>,
+}
+
+impl SlashCommand {
+    /// Register a command and the bot answering it, `reserved` are names of built-in commands
+    pub async fn create(
+        chat_id: i64,
+        creator: &User,
+        input: &CreateSlashCommand,
+        reserved: &[&str],
+        config: &OutboundConfig,
+        pool: &PgPool,
+    ) -> Result<Self, AppError> {
+        input.check()?;
+        if reserved.contains(&input.name.as_str()) {
+            return Err(AppError::InvalidInput(format!(
+                "/{} is a built-in command",
+                input.name
+            )));
+        }
+        check_url(&input.url, config).await?;
+        Chat::find_for_member(chat_id, creator.id, pool).await?;
+        if Self::find(chat_id, &input.name, pool).await?.is_some() {
+            return Err(AppError::InvalidInput(format!(
+                "command /{} already exists",
+                input.name
+            )));
+        }
+
+        let mut tx = pool.begin().await?;
+        let bot = User::create_bot(
+            creator.id,
+            &CreateBot {
+                fullname: format!("/{}", input.name),
+            },
+            &mut tx,
+        )
+        .await?;
+        Chat::add_member(chat_id, bot.id, &mut *tx).await?;
+        let command = sqlx::query_as(
+            r#"
+            INSERT INTO slash_commands (chat_id, bot_id, creator_id, name, url, secret, description)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, chat_id, bot_id, creator_id, name, url, secret, description, created_at
+            "#,
+        )
+        .bind(chat_id)
+        .bind(bot.id)
+        .bind(creator.id)
+        .bind(&input.name)
+        .bind(input.url.trim())
+        .bind(generate_token())
+        .bind(&input.description)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(command)
+    }
+
+    /// List commands of a chat the user is a member of
+    pub async fn list(chat_id: i64, user_id: i64, pool: &PgPool) -> Result<Vec<Self>, AppError> {
+        Chat::find_for_member(chat_id, user_id, pool).await?;
+        let commands = sqlx::query_as(
+            r#"
+            SELECT id, chat_id, bot_id, creator_id, name, url, secret, description, created_at
+            FROM slash_commands WHERE chat_id = $1
+            ORDER BY name
+            "#,
+        )
+        .bind(chat_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(commands)
+    }
+
+    pub async fn find(chat_id: i64, name: &str, pool: &PgPool) -> Result<Option<Self>, AppError> {
+        let command = sqlx::query_as(
+            r#"
+            SELECT id, chat_id, bot_id, creator_id, name, url, secret, description, created_at
+            FROM slash_commands WHERE chat_id = $1 AND name = $2
+            "#,
+        )
+        .bind(chat_id)
+        .bind(name)
+        .fetch_optional(pool)
+        .await?;
+        Ok(command)
+    }
+
+    /// Delete a command, its bot leaves the chat
+    pub async fn delete(id: i64, user_id: i64, pool: &PgPool) -> Result<(), AppError> {
+        let command: Self = sqlx::query_as(
+            r#"
+            SELECT id, chat_id, bot_id, creator_id, name, url, secret, description, created_at
+            FROM slash_commands WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("command {}", id)))?;
+        Chat::find_for_member(command.chat_id, user_id, pool).await?;
+
+        let mut tx = pool.begin().await?;
+        sqlx::query("DELETE FROM slash_commands WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        Chat::remove_member(command.chat_id, command.bot_id, &mut *tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+impl Validate for CreateSlashCommand {
+    fn rules(&self, v: &mut Validator) {
+        v.field("name", &self.name).required().max_chars(32);
+        v.check(
+            "name",
+            self.name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_'),
+            "must only contain a-z, 0-9, - or _",
+        );
+        v.field("url", &self.url).url();
+        v.optional("description", self.description.as_deref())
+            .max_chars(250);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::AppError;
+use sha1::{Digest, Sha1};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// An uploaded file, addressed by the sha1 of its content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatFile {
+    pub ext: String,
+    pub hash: String,
+}
+
+impl ChatFile {
+    pub fn new(filename: &str, data: &[u8]) -> Self {
+        let hash = Sha1::digest(data);
+        let ext = match filename.rsplit_once('.') {
+            Some((_, ext)) if !ext.is_empty() => ext.to_ascii_lowercase(),
+            _ => "txt".to_string(),
+        };
+        Self {
+            ext,
+            hash: hex::encode(hash),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("/files/{}", self.hash_to_path())
+    }
+
+    pub fn path(&self, base_dir: &Path) -> PathBuf {
+        base_dir.join(self.hash_to_path())
+    }
+
+    // split the hash so a single directory never holds too many files: abc/def/0123...ext
+    fn hash_to_path(&self) -> String {
+        let (part1, part2) = self.hash.split_at(3);
+        let (part2, part3) = part2.split_at(3);
+        format!("{}/{}/{}.{}", part1, part2, part3, self.ext)
+    }
+}
+
+impl FromStr for ChatFile {
+    type Err = AppError;
+
+    // parse a url like /files/abc/def/0123456789.png
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AppError::InvalidInput(format!("invalid file url: {}", s));
+        let path = s.strip_prefix("/files/").ok_or_else(invalid)?;
+        let parts: Vec<&str> = path.split('/').collect();
+        let [part1, part2, rest] = parts[..] else {
+            return Err(invalid());
+        };
+        let (part3, ext) = rest.rsplit_once('.').ok_or_else(invalid)?;
+        let hash = format!("{}{}{}", part1, part2, part3);
+        let valid = hash.len() == 40
+            && hash.chars().all(|c| c.is_ascii_hexdigit())
+            && part1.len() == 3
+            && part2.len() == 3
+            && !ext.is_empty()
+            && ext.chars().all(|c| c.is_ascii_alphanumeric());
+        if !valid {
+            return Err(invalid());
+        }
+        Ok(Self {
+            ext: ext.to_string(),
+            hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chat_file_new_should_work() {
+        let file = ChatFile::new("test.PNG", b"hello world");
+        assert_eq!(file.ext, "png");
+        assert_eq!(file.hash, "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed");
+        assert_eq!(
+            file.url(),
+            "/files/2aa/e6c/35c94fcfb415dbe95f408b9ce91ee846ed.png"
+        );
+    }
+
+    #[test]
+    fn chat_file_from_str_should_work() {
+        let file = ChatFile::new("test.png", b"hello world");
+        let parsed: ChatFile = file.url().parse().unwrap();
+        assert_eq!(parsed, file);
+
+        assert!("/files/../../etc/passwd".parse::<ChatFile>().is_err());
+        assert!("https://example.com/a.png".parse::<ChatFile>().is_err());
+    }
+}
@@ -1,12 +1,49 @@
+mod api_token;
+mod bookmark;
+mod bot;
+mod chat;
+mod command;
+mod file;
+mod mention;
+mod message;
+mod mfa;
+mod outgoing_webhook;
+mod password;
+mod pin;
+mod scheduled_message;
+mod signin_attempt;
 mod user;
+mod verification;
+mod webhook;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
 
-pub use user::{CreateUser, SigninUser};
+pub use api_token::{ApiToken, ApiTokenAuth, CreateApiToken, CreatedApiToken};
+pub use bookmark::{Bookmark, CreateBookmark};
+pub use bot::CreateBot;
+pub use chat::{Chat, ChatType, UpdateChat};
+pub use command::{CreateSlashCommand, SlashCommand};
+pub use file::ChatFile;
+pub use mention::Mention;
+pub use message::{CreateMessage, ListMessages, Message, MessageKind, SearchHit, SearchMessages};
+use message::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+pub use mfa::{RecoveryCodes, SigninMfa, TotpCode, TotpEnrollment};
+pub use outgoing_webhook::{
+    CreateOutgoingWebhook, DeliveryStatus, ListDeliveries, OutgoingWebhook, PendingDelivery,
+    WebhookDelivery,
+};
+pub use password::{ChangePassword, ForgotPassword, ResetPassword, PASSWORD_RESET_TTL_MINUTES};
+pub use pin::Pin;
+pub use scheduled_message::{CreateScheduledMessage, ListScheduledMessages, ScheduledMessage};
+pub use signin_attempt::SigninAttempt;
+pub use user::{CreateUser, ListUsers, SigninUser, UpdateUser};
+pub use verification::{ResendVerification, VerifyEmail, EMAIL_VERIFICATION_TTL_HOURS};
+pub use webhook::{CreateIncomingWebhook, IncomingWebhook, WebhookPayload};
 
-#[derive(Debug, Clone, FromRow, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, PartialEq, ToSchema)]
 pub struct User {
     pub id: i64,
     pub fullname: String,
@@ -14,5 +51,20 @@ pub struct User {
     #[sqlx(default)]
     #[serde(skip)]
     pub password_hash: Option<String>,
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_text: Option<String>,
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified_at: Option<DateTime<Utc>>,
+    #[sqlx(default)]
+    #[serde(default)]
+    pub is_bot: bool,
     pub created_at: DateTime<Utc>,
 }
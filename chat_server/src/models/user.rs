@@ -1,36 +1,144 @@
-use crate::{AppError, User};
+use crate::{
+    validation::{Validate, Validator},
+    AppError, User,
+};
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use std::mem;
+use std::{mem, sync::OnceLock};
+use utoipa::{IntoParams, ToSchema};
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CreateUser {
     pub fullname: String,
     pub email: String,
     pub password: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SigninUser {
     pub email: String,
     pub password: String,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListUsers {
+    /// prefix of fullname or email, case-insensitive, required
+    pub q: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Profile update, `None` leaves a field untouched and an empty string clears it
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct UpdateUser {
+    pub fullname: Option<String>,
+    pub avatar_url: Option<String>,
+    pub status_text: Option<String>,
+    pub timezone: Option<String>,
+}
+
 impl User {
-    /// Find a user by email
+    /// Find a user by email, case-insensitively
     pub async fn find_by_email(email: &str, pool: &PgPool) -> Result<Option<Self>, AppError> {
-        let user =
-            sqlx::query_as("SELECT id, fullname, email, created_at FROM users WHERE email = $1")
-                .bind(email)
-                .fetch_optional(pool)
-                .await?;
+        let user = sqlx::query_as(
+            r#"
+            SELECT id, fullname, email, email_verified_at, created_at FROM users
+            WHERE lower(email) = lower($1)
+            "#,
+        )
+        .bind(email)
+        .fetch_optional(pool)
+        .await?;
+        Ok(user)
+    }
+
+    /// Find a user by id, including profile fields
+    pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<Option<Self>, AppError> {
+        let user = sqlx::query_as(
+            r#"
+            SELECT id, fullname, email, avatar_url, status_text, timezone, email_verified_at,
+                is_bot, created_at
+            FROM users WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
         Ok(user)
     }
 
+    /// List users who share a chat with the given user and whose fullname or email starts with
+    /// the given prefix, bots are left out
+    pub async fn list(
+        user_id: i64,
+        input: &ListUsers,
+        pool: &PgPool,
+    ) -> Result<Vec<Self>, AppError> {
+        let prefix = input.q.as_deref().unwrap_or_default().trim().to_lowercase();
+        if prefix.is_empty() {
+            return Err(AppError::InvalidInput("search query is empty".to_string()));
+        }
+        let pattern = format!("{}%", escape_like(&prefix));
+        let limit = input
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let offset = input.offset.unwrap_or(0).max(0);
+
+        let users = sqlx::query_as(
+            r#"
+            SELECT id, fullname, email, avatar_url, status_text, timezone, email_verified_at,
+                is_bot, created_at
+            FROM users u
+            WHERE NOT is_bot AND (lower(fullname) LIKE $2 OR lower(email) LIKE $2)
+                AND EXISTS(SELECT 1 FROM chats WHERE $1 = ANY(members) AND u.id = ANY(members))
+            ORDER BY fullname, id
+            LIMIT $3 OFFSET $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(pattern)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+        Ok(users)
+    }
+
+    /// Update profile fields of a user
+    pub async fn update(id: i64, input: &UpdateUser, pool: &PgPool) -> Result<Self, AppError> {
+        input.check()?;
+        let user = sqlx::query_as(
+            r#"
+            UPDATE users SET
+                fullname = COALESCE($2, fullname),
+                avatar_url = CASE WHEN $3::text IS NULL THEN avatar_url ELSE NULLIF($3, '') END,
+                status_text = CASE WHEN $4::text IS NULL THEN status_text ELSE NULLIF($4, '') END,
+                timezone = CASE WHEN $5::text IS NULL THEN timezone ELSE NULLIF($5, '') END
+            WHERE id = $1
+            RETURNING id, fullname, email, avatar_url, status_text, timezone, email_verified_at,
+                is_bot, created_at
+            "#,
+        )
+        .bind(id)
+        .bind(input.fullname.as_deref().map(str::trim))
+        .bind(&input.avatar_url)
+        .bind(input.status_text.as_deref().map(str::trim))
+        .bind(&input.timezone)
+        .fetch_optional(pool)
+        .await?;
+        user.ok_or_else(|| AppError::NotFound(format!("user {}", id)))
+    }
+
     /// Create a new user
     pub async fn create(input: &CreateUser, pool: &PgPool) -> Result<Self, AppError> {
         let password_hash = hash_password(&input.password)?;
@@ -46,22 +154,25 @@ impl User {
             RETURNING id, fullname, email, created_at
             "#,
         )
-            .bind(&input.email)
-            .bind(&input.fullname)
-            .bind(password_hash)
-            .fetch_one(pool)
-            .await?;
+        .bind(&input.email)
+        .bind(&input.fullname)
+        .bind(password_hash)
+        .fetch_one(pool)
+        .await?;
         Ok(user)
     }
 
     /// Verify email and password
     pub async fn verify(input: &SigninUser, pool: &PgPool) -> Result<Option<Self>, AppError> {
         let user: Option<User> = sqlx::query_as(
-            "SELECT id, fullname, email, password_hash, created_at FROM users WHERE email = $1",
+            r#"
+            SELECT id, fullname, email, password_hash, email_verified_at, created_at
+            FROM users WHERE email = $1 AND NOT is_bot
+            "#,
         )
-            .bind(&input.email)
-            .fetch_optional(pool)
-            .await?;
+        .bind(&input.email)
+        .fetch_optional(pool)
+        .await?;
         match user {
             Some(mut user) => {
                 let password_hash = mem::take(&mut user.password_hash);
@@ -73,12 +184,66 @@ impl User {
                     Ok(None)
                 }
             }
-            None => Ok(None),
+            None => {
+                // hash anyway so the response time doesn't reveal whether the email exists
+                verify_password(&input.password, dummy_password_hash())?;
+                Ok(None)
+            }
         }
     }
 }
 
-fn hash_password(password: &str) -> Result<String, AppError> {
+impl Validate for CreateUser {
+    fn rules(&self, v: &mut Validator) {
+        v.field("fullname", &self.fullname).required().max_chars(64);
+        v.field("email", &self.email).email().max_chars(64);
+        v.field("password", &self.password).password();
+    }
+}
+
+impl Validate for SigninUser {
+    fn rules(&self, v: &mut Validator) {
+        v.field("email", &self.email).required().max_chars(64);
+        v.field("password", &self.password)
+            .required()
+            .max_chars(128);
+    }
+}
+
+impl Validate for UpdateUser {
+    fn rules(&self, v: &mut Validator) {
+        v.optional("fullname", self.fullname.as_deref())
+            .required()
+            .max_chars(64);
+        v.optional("avatar_url", self.avatar_url.as_deref())
+            .max_chars(256);
+        v.optional("status_text", self.status_text.as_deref())
+            .max_chars(128);
+        v.optional("timezone", self.timezone.as_deref())
+            .max_chars(64);
+        if let Some(tz) = self.timezone.as_deref() {
+            v.check(
+                "timezone",
+                tz.is_empty() || tz.parse::<Tz>().is_ok(),
+                format!("unknown timezone: {}", tz),
+            );
+        }
+    }
+}
+
+fn dummy_password_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hash_password("dummy password").expect("hash dummy password"))
+}
+
+// escape LIKE wildcards so user input is matched literally
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+pub(crate) fn hash_password(password: &str) -> Result<String, AppError> {
     let salt = SaltString::generate(&mut OsRng);
 
     // Argon2 with default params (Argon2id v19)
@@ -92,7 +257,7 @@ fn hash_password(password: &str) -> Result<String, AppError> {
     Ok(password_hash)
 }
 
-fn verify_password(password: &str, password_hash: &str) -> Result<bool, AppError> {
+pub(crate) fn verify_password(password: &str, password_hash: &str) -> Result<bool, AppError> {
     let argon2 = Argon2::default();
     let password_hash = PasswordHash::new(password_hash)?;
 
@@ -112,6 +277,11 @@ impl User {
             fullname: fullname.to_string(),
             email: email.to_string(),
             password_hash: None,
+            avatar_url: None,
+            status_text: None,
+            timezone: None,
+            email_verified_at: None,
+            is_bot: false,
             created_at: chrono::Utc::now(),
         }
     }
@@ -193,6 +363,8 @@ mod tests {
         let user = user.unwrap();
         assert_eq!(user.email, input.email);
         assert_eq!(user.fullname, input.fullname);
+        let user = User::find_by_email("TChen@Acme.org", &pool).await?;
+        assert_eq!(user.map(|u| u.id), Some(1));
 
         let input = SigninUser::new(&input.email, &input.password);
         let user = User::verify(&input, &pool).await?;
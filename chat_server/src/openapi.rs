@@ -0,0 +1,210 @@
+use crate::handlers::*;
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+const OPENAPI_PATH: &str = "/api/openapi.json";
+const DOCS_PATH: &str = "/api/docs";
+
+/// The OpenAPI document of /api, built from the `#[utoipa::path]` of the handlers
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Chat API"),
+    modifiers(&BearerAuth),
+    security(("token" = [])),
+    paths(
+        signup_handler,
+        signin_handler,
+        signin_mfa_handler,
+        forgot_password_handler,
+        reset_password_handler,
+        verify_email_handler,
+        resend_verification_handler,
+        enroll_totp_handler,
+        confirm_totp_handler,
+        disable_totp_handler,
+        list_users_handler,
+        get_user_handler,
+        update_profile_handler,
+        change_password_handler,
+        list_chat_handler,
+        create_chat_handler,
+        update_chat_handler,
+        delete_chat_handler,
+        list_pins_handler,
+        pin_message_handler,
+        unpin_message_handler,
+        send_message_handler,
+        list_message_handler,
+        list_mentions_handler,
+        search_messages_handler,
+        list_bookmarks_handler,
+        create_bookmark_handler,
+        delete_bookmark_handler,
+        schedule_message_handler,
+        list_scheduled_messages_handler,
+        cancel_scheduled_message_handler,
+        upload_handler,
+        file_handler,
+        list_webhooks_handler,
+        create_webhook_handler,
+        regenerate_webhook_handler,
+        delete_webhook_handler,
+        incoming_webhook_handler,
+        list_outgoing_webhooks_handler,
+        create_outgoing_webhook_handler,
+        delete_outgoing_webhook_handler,
+        list_deliveries_handler,
+        retry_delivery_handler,
+        list_commands_handler,
+        create_command_handler,
+        delete_command_handler,
+        list_bots_handler,
+        create_bot_handler,
+        list_tokens_handler,
+        create_token_handler,
+        revoke_token_handler,
+    ),
+    // only mentioned in descriptions, the other schemas are collected from the paths
+    components(schemas(MfaPendingOutput)),
+    tags(
+        (name = "auth", description = "Accounts, signing in and two-factor authentication"),
+        (name = "users"),
+        (name = "chats"),
+        (name = "messages", description = "Messages, mentions, bookmarks and search"),
+        (name = "files"),
+        (name = "integrations", description = "Webhooks, slash commands, bots and api tokens"),
+    )
+)]
+pub(crate) struct ApiDoc;
+
+// the token of /api/signin, or an api token
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// Swagger UI at /api/docs, reading the document from /api/openapi.json
+pub(crate) fn docs() -> SwaggerUi {
+    SwaggerUi::new(DOCS_PATH).url(OPENAPI_PATH, ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::CreateUser, router, AppConfig, AppState, User};
+    use anyhow::Result;
+    use axum::{
+        body::Body,
+        http::{header::AUTHORIZATION, Method, Request, StatusCode},
+    };
+    use std::collections::BTreeSet;
+    use tower::ServiceExt;
+    use utoipa::openapi::PathItem;
+
+    const METHODS: [Method; 5] = [
+        Method::GET,
+        Method::POST,
+        Method::PUT,
+        Method::PATCH,
+        Method::DELETE,
+    ];
+
+    #[tokio::test]
+    async fn openapi_should_match_routes() -> Result<()> {
+        let mut config = AppConfig::load()?;
+        // every route is probed with every method
+        config.rate_limit.api.burst = 1000;
+        config.rate_limit.public.burst = 1000;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        let token = state.keys().ek.sign(user)?;
+        let app = router(state);
+
+        let req = Request::get(OPENAPI_PATH).body(Body::empty())?;
+        assert_eq!(app.clone().oneshot(req).await?.status(), StatusCode::OK);
+
+        // axum can't list its routes, but its Debug output names their paths
+        let routed: BTreeSet<String> = format!("{:?}", app)
+            .split('"')
+            .skip(1)
+            .step_by(2)
+            .filter(|path| path.starts_with("/api/"))
+            .filter(|path| !path.starts_with(DOCS_PATH) && *path != OPENAPI_PATH)
+            .map(openapi_path)
+            .collect();
+        // an empty set would mean the Debug output changed, not that nothing is routed
+        assert!(
+            routed.contains("/api/chat/{id}"),
+            "routes not found in {:?}",
+            routed
+        );
+        let spec = ApiDoc::openapi();
+        let documented: BTreeSet<String> = spec.paths.paths.keys().cloned().collect();
+        let drifted: Vec<_> = routed.symmetric_difference(&documented).collect();
+        assert!(drifted.is_empty(), "paths not in both: {:?}", drifted);
+
+        // methods without a route are answered with 405
+        let mut drifted = Vec::new();
+        for (path, item) in &spec.paths.paths {
+            let uri = path
+                .split('/')
+                .map(|segment| {
+                    if segment.starts_with('{') {
+                        "1"
+                    } else {
+                        segment
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            for method in METHODS {
+                let req = Request::builder()
+                    .method(method.clone())
+                    .uri(&uri)
+                    .header(AUTHORIZATION, format!("Bearer {}", token))
+                    .body(Body::empty())?;
+                let status = app.clone().oneshot(req).await?.status();
+                let routed = status != StatusCode::METHOD_NOT_ALLOWED;
+                if routed != has_operation(item, &method) {
+                    drifted.push(format!("{} {} (routed: {})", method, path, routed));
+                }
+            }
+        }
+        assert!(drifted.is_empty(), "spec and routes differ: {:?}", drifted);
+        Ok(())
+    }
+
+    // /chat/:id and /files/*path are /chat/{id} and /files/{path} in the spec
+    fn openapi_path(path: &str) -> String {
+        path.split('/')
+            .map(|segment| match segment.strip_prefix([':', '*']) {
+                Some(name) => format!("{{{}}}", name),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn has_operation(item: &PathItem, method: &Method) -> bool {
+        let operation = match *method {
+            Method::GET => &item.get,
+            Method::POST => &item.post,
+            Method::PUT => &item.put,
+            Method::PATCH => &item.patch,
+            Method::DELETE => &item.delete,
+            _ => return false,
+        };
+        operation.is_some()
+    }
+}
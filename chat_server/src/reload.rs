@@ -0,0 +1,242 @@
+use crate::{AppConfig, AppError, AppState, Keys};
+use serde_yaml::Value;
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tracing::{info, warn};
+
+// the config and key files are polled for changes, SIGHUP reloads right away
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// fields applied at runtime, changes to anything else need a restart
+const RELOADABLE: &[&str] = &[
+    "rate_limit",
+    "upload",
+    "log.level",
+    "auth.sk",
+    "auth.sk_file",
+    "auth.pk",
+    "auth.pk_file",
+];
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ReloadReport {
+    /// reloadable fields that changed and are now in effect
+    pub(crate) applied: Vec<String>,
+    /// fields that differ from the config the server started with
+    pub(crate) needs_restart: Vec<String>,
+}
+
+/// Reload the config when its file or a key file changes or on SIGHUP, an invalid config is
+/// logged and ignored
+pub(crate) async fn watch(state: AppState) {
+    let path = state.config.path.clone();
+    let mut current = state.config.clone();
+    let mut modified = modified_at(&watched_files(&current));
+    let mut hangup = signal(SignalKind::hangup())
+        .inspect_err(|e| warn!("listen for SIGHUP failed: {}", e))
+        .ok();
+    loop {
+        let by_signal = tokio::select! {
+            _ = recv(&mut hangup) => true,
+            _ = tokio::time::sleep(POLL_INTERVAL) => false,
+            _ = state.shutdown.wait() => return,
+        };
+        let last_modified = modified_at(&watched_files(&current));
+        if !by_signal && last_modified == modified {
+            continue;
+        }
+        modified = last_modified;
+
+        let new = match AppConfig::load_from(Some(path.clone())) {
+            Ok(new) => new,
+            Err(e) => {
+                warn!("reload config failed, keeping the current one: {:#}", e);
+                continue;
+            }
+        };
+        match apply(&state, &current, &new) {
+            Ok(report) => {
+                if !report.applied.is_empty() {
+                    info!("config reloaded: {}", report.applied.join(", "));
+                }
+                if !report.needs_restart.is_empty() {
+                    warn!(
+                        "config changes need a restart: {}",
+                        report.needs_restart.join(", ")
+                    );
+                }
+                current = new;
+                // the key files may have moved
+                modified = modified_at(&watched_files(&current));
+            }
+            Err(e) => warn!("apply reloaded config failed: {}", e),
+        }
+    }
+}
+
+/// Swap the reloadable parts of the state for those of `new`
+pub(crate) fn apply(
+    state: &AppState,
+    current: &AppConfig,
+    new: &AppConfig,
+) -> Result<ReloadReport, AppError> {
+    let applied: Vec<String> = changed_fields(current, new)
+        .into_iter()
+        .filter(|field| is_reloadable(field))
+        .collect();
+    let needs_restart = changed_fields(&state.config, new)
+        .into_iter()
+        .filter(|field| !is_reloadable(field))
+        .collect();
+
+    // load everything that can fail before swapping anything
+    if applied.iter().any(|field| field.starts_with("auth.")) {
+        state.keys.store(Keys::load(&new.auth)?.into());
+    }
+    chat_core::set_level(&new.log.level)?;
+    let rate_limit = &new.rate_limit;
+    state.public_limiter.set_rule(rate_limit.public);
+    state.api_limiter.set_rule(rate_limit.api);
+    state.api_ip_limiter.set_rule(rate_limit.api_ip);
+    state.webhook_limiter.set_rule(rate_limit.webhook);
+    state.upload.store(new.upload.clone().into());
+    Ok(ReloadReport {
+        applied,
+        needs_restart,
+    })
+}
+
+fn is_reloadable(field: &str) -> bool {
+    RELOADABLE.iter().any(|prefix| {
+        field == *prefix
+            || field
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+// dotted paths of the fields that differ, e.g. "rate_limit.api.burst"
+fn changed_fields(old: &AppConfig, new: &AppConfig) -> Vec<String> {
+    let old = serde_yaml::to_value(old).expect("config should serialize");
+    let new = serde_yaml::to_value(new).expect("config should serialize");
+    let mut fields = Vec::new();
+    diff("", &old, &new, &mut fields);
+    fields
+}
+
+fn diff(prefix: &str, old: &Value, new: &Value, fields: &mut Vec<String>) {
+    let (Value::Mapping(old), Value::Mapping(new)) = (old, new) else {
+        if old != new {
+            fields.push(prefix.to_string());
+        }
+        return;
+    };
+    let keys = old
+        .keys()
+        .chain(new.keys().filter(|key| !old.contains_key(*key)));
+    for key in keys {
+        let name = key.as_str().unwrap_or_default();
+        let field = if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        let old = old.get(key).unwrap_or(&Value::Null);
+        let new = new.get(key).unwrap_or(&Value::Null);
+        diff(&field, old, new, fields);
+    }
+}
+
+async fn recv(hangup: &mut Option<Signal>) {
+    match hangup {
+        Some(hangup) => {
+            hangup.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+// key files are read when the config is loaded, a rotated key only shows up in their mtime
+fn watched_files(config: &AppConfig) -> Vec<PathBuf> {
+    let auth = &config.auth;
+    [
+        Some(&config.path),
+        auth.sk_file.as_ref(),
+        auth.pk_file.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .cloned()
+    .collect()
+}
+
+fn modified_at(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| path.metadata().and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middlewares::RateLimitKey;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn reload_should_swap_reloadable_fields_only() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let mut new = state.config.clone();
+        new.rate_limit.webhook.burst = 1;
+        new.upload.max_file_mb = 1;
+        new.server.port = 7788;
+
+        let report = apply(&state, &state.config, &new)?;
+        assert_eq!(
+            report,
+            ReloadReport {
+                applied: vec![
+                    "rate_limit.webhook.burst".to_string(),
+                    "upload.max_file_mb".to_string()
+                ],
+                needs_restart: vec!["server.port".to_string()],
+            }
+        );
+        assert_eq!(state.upload.load().max_file_mb, 1);
+        state.webhook_limiter.acquire(RateLimitKey::Webhook(1))?;
+        assert!(state
+            .webhook_limiter
+            .acquire(RateLimitKey::Webhook(1))
+            .is_err());
+
+        // applied changes aren't reported again, the port still needs a restart
+        let report = apply(&state, &new, &new)?;
+        assert!(report.applied.is_empty());
+        assert_eq!(report.needs_restart, vec!["server.port"]);
+        Ok(())
+    }
+
+    #[test]
+    fn key_files_should_be_watched() -> Result<()> {
+        let mut config = AppConfig::load()?;
+        config.auth.sk_file = Some("fixtures/encoding.pem".into());
+        let files = watched_files(&config);
+        assert_eq!(
+            files,
+            vec![config.path.clone(), "fixtures/encoding.pem".into()]
+        );
+
+        let key = std::env::temp_dir().join(format!("chat_server_{}.pem", std::process::id()));
+        std::fs::write(&key, "old")?;
+        config.auth.pk_file = Some(key.clone());
+        let before = modified_at(&watched_files(&config));
+        let file = std::fs::File::options().write(true).open(&key)?;
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))?;
+        assert_ne!(modified_at(&watched_files(&config)), before);
+        std::fs::remove_file(&key)?;
+        Ok(())
+    }
+}
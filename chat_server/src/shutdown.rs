@@ -0,0 +1,52 @@
+use tokio::{
+    signal::{
+        self,
+        unix::{signal, SignalKind},
+    },
+    sync::watch,
+};
+use tracing::warn;
+
+/// Flips once when the server starts shutting down, background tasks watch it to stop
+#[derive(Debug)]
+pub(crate) struct Shutdown(watch::Sender<bool>);
+
+impl Shutdown {
+    pub(crate) fn new() -> Self {
+        Self(watch::channel(false).0)
+    }
+
+    pub(crate) fn trigger(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub(crate) fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once shutdown is triggered, right away if it already is
+    pub(crate) async fn wait(&self) {
+        let mut rx = self.0.subscribe();
+        // the sender lives as long as self, so this can't fail
+        let _ = rx.wait_for(|triggered| *triggered).await;
+    }
+}
+
+/// Resolves on ctrl-c or SIGTERM
+pub async fn shutdown_signal() {
+    let terminate = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                warn!("listen for SIGTERM failed: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    tokio::select! {
+        _ = signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+}
@@ -0,0 +1,104 @@
+use axum::http::HeaderMap;
+use opentelemetry::{global, trace::TraceContextExt, Context};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use std::collections::HashMap;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+const TRACEPARENT: &str = "traceparent";
+
+/// The trace a request belongs to, from its `traceparent` header
+pub(crate) fn parent_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// Add the `traceparent` of the current span to an outgoing request
+pub(crate) fn inject_context(headers: &mut HeaderMap) {
+    let cx = Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut HeaderInjector(headers))
+    });
+}
+
+/// The `traceparent` of the current span, for messages that aren't http requests
+pub(crate) fn traceparent() -> Option<String> {
+    let cx = Span::current().context();
+    let mut carrier = HashMap::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&cx, &mut carrier));
+    carrier.remove(TRACEPARENT)
+}
+
+/// The hex trace id of the span, None when it isn't traced
+pub(crate) fn trace_id(span: &Span) -> Option<String> {
+    let cx = span.context();
+    let span_context = cx.span().span_context().clone();
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use axum::{body::Bytes, routing::post, Router};
+    use chat_core::{tracer_provider, TracingConfig};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use tokio::{net::TcpListener, sync::mpsc};
+    use tracing::info_span;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn spans_should_continue_the_trace_and_be_exported() -> Result<()> {
+        // stands in for the collector, OTLP/HTTP posts protobuf to /v1/traces
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/v1/traces",
+            post(move |body: Bytes| async move {
+                tx.send(body).ok();
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let config = TracingConfig {
+            otlp_endpoint: Some(format!("http://{}/v1/traces", addr)),
+            service_name: None,
+            sample_ratio: 0.0,
+        };
+        let provider = tracer_provider("chat_server_test", &config)?;
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        let mut incoming = HeaderMap::new();
+        incoming.insert(
+            TRACEPARENT,
+            format!("00-{}-00f067aa0ba902b7-01", TRACE_ID).parse()?,
+        );
+        let mut outgoing = HeaderMap::new();
+        let (id, payload) = tracing::subscriber::with_default(subscriber, || {
+            let span = info_span!("request");
+            span.set_parent(parent_context(&incoming));
+            let _enter = span.enter();
+            inject_context(&mut outgoing);
+            (trace_id(&span), traceparent())
+        });
+
+        // the sampled parent wins over the ratio of 0
+        assert_eq!(id.as_deref(), Some(TRACE_ID));
+        let payload = payload.expect("traceparent should be set");
+        assert!(payload.starts_with(&format!("00-{}-", TRACE_ID)));
+        assert_eq!(outgoing[TRACEPARENT], payload.as_str());
+
+        tokio::task::spawn_blocking(move || provider.force_flush()).await?;
+        let body = rx.recv().await.expect("collector should receive spans");
+        let raw_id = opentelemetry::trace::TraceId::from_hex(TRACE_ID)?.to_bytes();
+        assert!(body.windows(raw_id.len()).any(|w| w == raw_id));
+        Ok(())
+    }
+}
@@ -4,6 +4,9 @@ use jwt_simple::prelude::*;
 const JWT_DURATION: u64 = 60 * 60 * 24 * 7;
 const JWT_ISS: &str = "chat_server";
 const JWT_AUD: &str = "chat_web";
+// short-lived token proving the password step of a two-factor signin
+const MFA_JWT_DURATION: u64 = 60 * 5;
+const MFA_JWT_AUD: &str = "chat_mfa";
 
 pub struct EncodingKey(Ed25519KeyPair);
 
@@ -16,8 +19,17 @@ impl EncodingKey {
     }
 
     pub fn sign(&self, user: impl Into<User>) -> Result<String, AppError> {
-        let claims = Claims::with_custom_claims(user.into(), Duration::from_secs(JWT_DURATION));
-        let claims = claims.with_issuer(JWT_ISS).with_audience(JWT_AUD);
+        self.sign_with(user.into(), JWT_AUD, JWT_DURATION)
+    }
+
+    /// Sign a pending token that can only be exchanged for a real one with a second factor
+    pub fn sign_mfa(&self, user: impl Into<User>) -> Result<String, AppError> {
+        self.sign_with(user.into(), MFA_JWT_AUD, MFA_JWT_DURATION)
+    }
+
+    fn sign_with(&self, user: User, aud: &str, duration: u64) -> Result<String, AppError> {
+        let claims = Claims::with_custom_claims(user, Duration::from_secs(duration));
+        let claims = claims.with_issuer(JWT_ISS).with_audience(aud);
         Ok(self.0.sign(claims)?)
     }
 }
@@ -29,9 +41,17 @@ impl DecodingKey {
 
     #[allow(unused)]
     pub fn verify(&self, token: &str) -> Result<User, AppError> {
+        self.verify_with(token, JWT_AUD)
+    }
+
+    pub fn verify_mfa(&self, token: &str) -> Result<User, AppError> {
+        self.verify_with(token, MFA_JWT_AUD)
+    }
+
+    fn verify_with(&self, token: &str, aud: &str) -> Result<User, AppError> {
         let opts = VerificationOptions {
             allowed_issuers: Some(HashSet::from_strings(&[JWT_ISS])),
-            allowed_audiences: Some(HashSet::from_strings(&[JWT_AUD])),
+            allowed_audiences: Some(HashSet::from_strings(&[aud])),
             ..Default::default()
         };
 
@@ -60,4 +80,21 @@ mod tests {
         assert_eq!(user, user2);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn mfa_token_should_not_be_accepted_as_access_token() -> Result<()> {
+        let encoding_pem = include_str!("../../fixtures/encoding.pem");
+        let decoding_pem = include_str!("../../fixtures/decoding.pem");
+        let ek = EncodingKey::load(encoding_pem)?;
+        let dk = DecodingKey::load(decoding_pem)?;
+
+        let user = User::new(1, "Tyr Chen", "tchen@acme.org");
+        let token = ek.sign_mfa(user.clone())?;
+        assert!(dk.verify(&token).is_err());
+        assert_eq!(dk.verify_mfa(&token)?, user);
+
+        let token = ek.sign(user)?;
+        assert!(dk.verify_mfa(&token).is_err());
+        Ok(())
+    }
 }
@@ -1,2 +1,11 @@
 mod jwt;
-pub use jwt::{DecodingKey,EncodingKey};
\ No newline at end of file
+mod outbound;
+mod signature;
+mod token;
+mod totp;
+
+pub use jwt::{DecodingKey, EncodingKey};
+pub use outbound::{check_url, outbound_client};
+pub use signature::sign_payload;
+pub use token::{generate_token, hash_token};
+pub use totp::Totp;
@@ -0,0 +1,143 @@
+use crate::{config::OutboundConfig, AppError};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::Policy,
+    Client, Url,
+};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::net::lookup_host;
+
+/// Client for requests to urls given by users (outgoing webhooks, slash commands). Hosts only
+/// resolve to public addresses unless they are in `outbound.allowed_hosts`, and redirects aren't
+/// followed since they could point anywhere.
+pub fn outbound_client(timeout: Duration, config: &OutboundConfig) -> reqwest::Result<Client> {
+    Client::builder()
+        .timeout(timeout)
+        .redirect(Policy::none())
+        .dns_resolver(Arc::new(PublicResolver(config.clone())))
+        .build()
+}
+
+/// Reject urls whose host is or resolves to a loopback, private or link-local address. Checked
+/// when a url is saved and again before every request, as literal ips bypass the resolver.
+pub async fn check_url(url: &str, config: &OutboundConfig) -> Result<(), AppError> {
+    let url = Url::parse(url.trim())
+        .map_err(|e| AppError::InvalidInput(format!("invalid url: {}", e)))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| AppError::InvalidInput("url has no host".to_string()))?;
+    if config.allows(host) {
+        return Ok(());
+    }
+    let port = url.port_or_known_default().unwrap_or(80);
+    // hosts that don't resolve yet are left to the resolver at request time
+    let addrs: Vec<IpAddr> = match lookup_host((host.trim_matches(['[', ']']), port)).await {
+        Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+        Err(_) => return Ok(()),
+    };
+    if addrs.iter().any(|ip| !is_public(*ip)) {
+        return Err(AppError::InvalidInput(format!(
+            "{} is not a public address",
+            host
+        )));
+    }
+    Ok(())
+}
+
+struct PublicResolver(OutboundConfig);
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allowed = self.0.allows(name.as_str());
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| allowed || is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+impl OutboundConfig {
+    fn allows(&self, host: &str) -> bool {
+        let host = host.trim_matches(['[', ']']);
+        self.allowed_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // carrier-grade nat, 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(ip.into()),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn check_url_should_reject_internal_addresses() {
+        let config = OutboundConfig::default();
+        for url in [
+            "http://127.0.0.1:9/hook",
+            "http://localhost/hook",
+            "http://10.0.0.1/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "http://[::ffff:10.0.0.1]/hook",
+            "http://[fd00::1]/hook",
+        ] {
+            assert!(check_url(url, &config).await.is_err(), "{}", url);
+        }
+        assert!(check_url("https://93.184.215.14/hook", &config)
+            .await
+            .is_ok());
+
+        let config = OutboundConfig {
+            allowed_hosts: vec!["LOCALHOST".to_string(), "::1".to_string()],
+        };
+        assert!(check_url("http://localhost/hook", &config).await.is_ok());
+        assert!(check_url("http://[::1]/hook", &config).await.is_ok());
+        assert!(check_url("http://127.0.0.1/hook", &config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn client_should_not_connect_to_internal_hosts() {
+        let client = outbound_client(Duration::from_secs(1), &OutboundConfig::default()).unwrap();
+        let err = client.get("http://localhost:9/").send().await.unwrap_err();
+        assert!(format!("{:?}", err).contains("no public address"));
+    }
+}
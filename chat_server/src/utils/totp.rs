@@ -0,0 +1,92 @@
+use crate::AppError;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+const TOTP_ISSUER: &str = "Chat";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+// accept codes from one step before or after to tolerate clock drift
+const TOTP_SKEW: u64 = 1;
+
+pub struct Totp(TOTP);
+
+impl Totp {
+    /// Create a TOTP with a new random secret
+    pub fn generate(account: &str) -> Self {
+        let secret = Secret::generate_secret()
+            .to_bytes()
+            .expect("generated secret should be valid");
+        Self::new(secret, account)
+    }
+
+    /// Restore a TOTP from a base32 encoded secret
+    pub fn from_secret(secret: &str, account: &str) -> Result<Self, AppError> {
+        let secret = Secret::Encoded(secret.to_string())
+            .to_bytes()
+            .map_err(|e| AppError::TotpError(format!("invalid secret: {:?}", e)))?;
+        Ok(Self::new(secret, account))
+    }
+
+    fn new(secret: Vec<u8>, account: &str) -> Self {
+        // ':' separates issuer and account name in the otpauth uri
+        let account = account.replace(':', "_");
+        Self(TOTP::new_unchecked(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            TOTP_SKEW as u8,
+            TOTP_STEP,
+            secret,
+            Some(TOTP_ISSUER.to_string()),
+            account,
+        ))
+    }
+
+    /// base32 encoded secret, for manual entry in an authenticator app
+    pub fn secret(&self) -> String {
+        self.0.get_secret_base32()
+    }
+
+    /// otpauth:// uri, usually rendered as a QR code
+    pub fn url(&self) -> String {
+        self.0.get_url()
+    }
+
+    /// The code for the step containing the given unix timestamp
+    pub fn code(&self, time: u64) -> String {
+        self.0.generate(time)
+    }
+
+    /// Verify a code at the given unix timestamp, returns the matched time step so callers can
+    /// reject replays of an already accepted code
+    pub fn verify(&self, code: &str, time: u64) -> Option<i64> {
+        let current = time / TOTP_STEP;
+        (current.saturating_sub(TOTP_SKEW)..=current + TOTP_SKEW)
+            .find(|step| constant_time_eq(self.code(step * TOTP_STEP).as_bytes(), code.as_bytes()))
+            .map(|step| step as i64)
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totp_should_work() -> Result<(), AppError> {
+        let totp = Totp::generate("tchen@acme.org");
+        assert!(totp
+            .url()
+            .starts_with("otpauth://totp/Chat:tchen%40acme.org?"));
+
+        let totp = Totp::from_secret(&totp.secret(), "tchen@acme.org")?;
+        let now = 1_700_000_000;
+        let code = totp.code(now);
+        assert_eq!(totp.verify(&code, now), Some((now / TOTP_STEP) as i64));
+        assert!(totp.verify(&code, now + TOTP_STEP).is_some());
+        assert!(totp.verify(&code, now + 3 * TOTP_STEP).is_none());
+        assert!(totp.verify("000000x", now).is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use crate::AppError;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+const MIN_PASSWORD_CHARS: usize = 8;
+// argon2 hashes whatever it gets, keep it from hashing megabytes
+const MAX_PASSWORD_CHARS: usize = 128;
+
+/// Request bodies declare the rules of their fields, `ValidJson` checks them before handlers run
+///
+/// ```ignore
+/// impl Validate for CreateUser {
+///     fn rules(&self, v: &mut Validator) {
+///         v.field("email", &self.email).email().max_chars(64);
+///     }
+/// }
+/// ```
+pub trait Validate {
+    fn rules(&self, v: &mut Validator);
+
+    fn check(&self) -> Result<(), AppError> {
+        let mut v = Validator::default();
+        self.rules(&mut v);
+        v.finish()
+    }
+}
+
+/// Error messages by field name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValidationErrors(pub BTreeMap<String, Vec<String>>);
+
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: ValidationErrors,
+}
+
+/// Rules for one field, an absent optional field passes all of them
+pub struct Field<'v, 's> {
+    validator: &'v mut Validator,
+    name: &'static str,
+    value: Option<&'s str>,
+}
+
+impl Validator {
+    pub fn field<'v, 's>(&'v mut self, name: &'static str, value: &'s str) -> Field<'v, 's> {
+        Field {
+            validator: self,
+            name,
+            value: Some(value),
+        }
+    }
+
+    pub fn optional<'v, 's>(
+        &'v mut self,
+        name: &'static str,
+        value: Option<&'s str>,
+    ) -> Field<'v, 's> {
+        Field {
+            validator: self,
+            name,
+            value,
+        }
+    }
+
+    /// Rules that don't fit a string field, e.g. on lists or numbers
+    pub fn check(&mut self, name: &'static str, valid: bool, message: impl Into<String>) {
+        if !valid {
+            self.error(name, message);
+        }
+    }
+
+    pub fn error(&mut self, name: &'static str, message: impl Into<String>) {
+        self.errors
+            .0
+            .entry(name.to_string())
+            .or_default()
+            .push(message.into());
+    }
+
+    fn finish(self) -> Result<(), AppError> {
+        if self.errors.0.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::ValidationError(self.errors))
+        }
+    }
+}
+
+impl Field<'_, '_> {
+    /// Not empty or only whitespace
+    pub fn required(self) -> Self {
+        self.rule(|v| !v.trim().is_empty(), || "must not be empty".to_string())
+    }
+
+    pub fn max_chars(self, max: usize) -> Self {
+        self.rule(
+            |v| v.chars().count() <= max,
+            || format!("must be at most {} characters", max),
+        )
+    }
+
+    pub fn email(self) -> Self {
+        self.rule(is_email, || "must be a valid email address".to_string())
+    }
+
+    /// At least 8 characters with a letter and a digit
+    pub fn password(self) -> Self {
+        self.rule(
+            |v| (MIN_PASSWORD_CHARS..=MAX_PASSWORD_CHARS).contains(&v.chars().count()),
+            || {
+                format!(
+                    "must be {} to {} characters",
+                    MIN_PASSWORD_CHARS, MAX_PASSWORD_CHARS
+                )
+            },
+        )
+        .rule(
+            |v| v.chars().any(char::is_alphabetic) && v.chars().any(|c| c.is_ascii_digit()),
+            || "must contain a letter and a digit".to_string(),
+        )
+    }
+
+    /// An absolute http or https url
+    pub fn url(self) -> Self {
+        self.rule(
+            |v| reqwest::Url::parse(v.trim()).is_ok_and(|u| matches!(u.scheme(), "http" | "https")),
+            || "must be an http or https url".to_string(),
+        )
+    }
+
+    fn rule(self, valid: impl FnOnce(&str) -> bool, message: impl FnOnce() -> String) -> Self {
+        if self.value.is_some_and(|v| !valid(v)) {
+            self.validator.error(self.name, message());
+        }
+        self
+    }
+}
+
+impl ValidationErrors {
+    /// A single error, for rules that can only be checked while handling the request
+    pub fn field(name: &str, message: impl Into<String>) -> Self {
+        Self(BTreeMap::from([(name.to_string(), vec![message.into()])]))
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<_> = self
+            .0
+            .iter()
+            .map(|(name, messages)| format!("{} {}", name, messages.join(", ")))
+            .collect();
+        write!(f, "{}", fields.join("; "))
+    }
+}
+
+// the shape only, whether the mailbox exists is up to verification
+fn is_email(s: &str) -> bool {
+    let Some((local, domain)) = s.rsplit_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !s.chars().any(char::is_whitespace)
+        && domain.split('.').count() >= 2
+        && domain.split('.').all(|label| !label.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Signup {
+        email: String,
+        password: String,
+        bio: Option<String>,
+    }
+
+    impl Validate for Signup {
+        fn rules(&self, v: &mut Validator) {
+            v.field("email", &self.email).email().max_chars(64);
+            v.field("password", &self.password).password();
+            v.optional("bio", self.bio.as_deref()).max_chars(10);
+        }
+    }
+
+    #[test]
+    fn validate_should_collect_field_errors() {
+        let input = Signup {
+            email: "tchen@acme.org".to_string(),
+            password: "Hunter42".to_string(),
+            bio: None,
+        };
+        assert!(input.check().is_ok());
+
+        let input = Signup {
+            email: format!("{}@acme", "t".repeat(64)),
+            password: "hunter".to_string(),
+            bio: Some("x".repeat(11)),
+        };
+        let Err(AppError::ValidationError(errors)) = input.check() else {
+            panic!("expect validation errors");
+        };
+        assert_eq!(errors.0["email"].len(), 2);
+        assert_eq!(errors.0["password"].len(), 2);
+        assert_eq!(errors.0["bio"], vec!["must be at most 10 characters"]);
+    }
+
+    #[test]
+    fn is_email_should_work() {
+        assert!(is_email("tchen@acme.org"));
+        assert!(is_email("t.chen+chat@mail.acme.org"));
+        assert!(!is_email("tchen"));
+        assert!(!is_email("@acme.org"));
+        assert!(!is_email("tchen@acme"));
+        assert!(!is_email("t chen@acme.org"));
+        assert!(!is_email("tchen@acme..org"));
+    }
+}
@@ -0,0 +1,11 @@
+mod scheduled_messages;
+mod webhook_delivery;
+
+use crate::{reload, AppState};
+
+/// Start the background workers, they stop when the server shuts down
+pub(crate) fn spawn(state: &AppState) {
+    tokio::spawn(webhook_delivery::run(state.clone()));
+    tokio::spawn(scheduled_messages::run(state.clone()));
+    tokio::spawn(reload::watch(state.clone()));
+}
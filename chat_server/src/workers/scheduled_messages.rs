@@ -0,0 +1,136 @@
+use crate::{
+    models::{Message, ScheduledMessage},
+    AppError, AppState,
+};
+use sqlx::Connection;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Poll for due scheduled messages until shutdown, a full batch is followed by another right away
+pub(crate) async fn run(state: AppState) {
+    let config = &state.config.scheduled_messages;
+    let poll_interval = Duration::from_millis(config.poll_interval_ms);
+    loop {
+        match send_due(&state).await {
+            Ok(n) if n >= config.batch_size as usize => continue,
+            Ok(_) => {}
+            Err(e) => warn!("send scheduled messages failed: {}", e),
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(poll_interval) => {}
+            _ = state.shutdown.wait() => return,
+        }
+    }
+}
+
+/// Send one batch of due messages through the normal message path, returns how many were handled.
+/// Each one is locked, sent and marked in its own transaction, so a crash in between sends
+/// nothing and leaves it pending to be picked up again after a restart. A send that fails for
+/// a transient reason is tried again after `retry_delay_secs`, up to `max_attempts` times.
+pub(crate) async fn send_due(state: &AppState) -> Result<usize, AppError> {
+    let config = &state.config.scheduled_messages;
+    let base_dir = &state.config.server.base_dir;
+    let mut n = 0;
+    while n < config.batch_size as usize {
+        let mut tx = state.pool.begin().await?;
+        let Some(scheduled) = ScheduledMessage::claim_due(&mut tx).await? else {
+            break;
+        };
+        let input = scheduled.to_message();
+        // a savepoint, so a failed send can be rolled back and still be marked
+        let mut send = tx.begin().await?;
+        let ret = Message::insert(
+            &input,
+            scheduled.chat_id,
+            scheduled.sender_id,
+            base_dir,
+            &mut send,
+        )
+        .await;
+        if ret.is_ok() {
+            send.commit().await?;
+        } else {
+            send.rollback().await?;
+        }
+        match ret {
+            Ok(message) => scheduled.mark_sent(message.id, &mut tx).await?,
+            // the sender left the chat, an attachment is gone or the content is no longer
+            // valid, retrying won't help
+            Err(
+                e @ (AppError::PermissionDenied(_)
+                | AppError::NotFound(_)
+                | AppError::InvalidInput(_)
+                | AppError::ValidationError(_)),
+            ) => {
+                info!("scheduled message {} failed: {}", scheduled.id, e);
+                scheduled.mark_failed(&e.to_string(), &mut tx).await?;
+            }
+            // retried after a delay, so one message that keeps failing doesn't hold up the rest
+            Err(e) => {
+                warn!("send scheduled message {} failed: {}", scheduled.id, e);
+                scheduled
+                    .record_failure(&e.to_string(), config, &mut tx)
+                    .await?;
+            }
+        }
+        tx.commit().await?;
+        n += 1;
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{CreateUser, ListMessages},
+        AppConfig, User,
+    };
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn due_messages_should_be_sent_once() -> Result<()> {
+        let config = AppConfig::load()?;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        let input = CreateUser::new("Alice Chen", "alice@acme.org", "Hunter42");
+        let other = User::create(&input, &state.pool).await?;
+        sqlx::query("INSERT INTO chats (name, type, members) VALUES ('general', 'group', $1)")
+            .bind(vec![user.id])
+            .execute(&state.pool)
+            .await?;
+        // the api only accepts future times, insert messages that are already due
+        sqlx::query(
+            r#"
+            INSERT INTO scheduled_messages (chat_id, sender_id, content, send_at)
+            VALUES (1, $1, 'standup in 5', now() - interval '1 minute'),
+                (1, $2, 'not a member', now() - interval '1 minute'),
+                (1, $1, 'tomorrow', now() + interval '1 day'),
+                (1, $1, ' ', now() - interval '1 minute')
+            "#,
+        )
+        .bind(user.id)
+        .bind(other.id)
+        .execute(&state.pool)
+        .await?;
+
+        assert_eq!(send_due(&state).await?, 3);
+        assert_eq!(send_due(&state).await?, 0);
+
+        let messages = Message::list(&ListMessages::default(), 1, user.id, &state.pool).await?;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content, "standup in 5");
+        let rows: Vec<(String, Option<i64>, Option<String>)> = sqlx::query_as(
+            "SELECT status::text, message_id, error FROM scheduled_messages ORDER BY id",
+        )
+        .fetch_all(&state.pool)
+        .await?;
+        assert_eq!(rows[0], ("sent".to_string(), Some(messages[0].id), None));
+        assert_eq!(rows[1].0, "failed");
+        assert!(rows[1].2.is_some());
+        assert_eq!(rows[2].0, "pending");
+        assert_eq!(rows[3].0, "failed");
+        Ok(())
+    }
+}
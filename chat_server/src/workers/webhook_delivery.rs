@@ -0,0 +1,292 @@
+use crate::{
+    models::{DeliveryStatus, PendingDelivery},
+    utils::{check_url, outbound_client, sign_payload},
+    AppError, AppState,
+};
+use chrono::Utc;
+use futures_util::future::join_all;
+use reqwest::{header::CONTENT_TYPE, Client};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const WEBHOOK_ID_HEADER: &str = "x-webhook-id";
+const WEBHOOK_EVENT_HEADER: &str = "x-webhook-event";
+const WEBHOOK_TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+const WEBHOOK_SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+/// Poll the delivery queue until shutdown, a full batch is followed by another one right away
+pub(crate) async fn run(state: AppState) {
+    let config = &state.config.outgoing_webhooks;
+    let client = outbound_client(
+        Duration::from_secs(config.timeout_secs),
+        &state.config.outbound,
+    )
+    .expect("build webhook http client failed");
+    let poll_interval = Duration::from_millis(config.poll_interval_ms);
+    loop {
+        match deliver_due(&state, &client).await {
+            Ok(n) if n >= config.batch_size as usize => continue,
+            Ok(_) => {}
+            Err(e) => warn!("deliver webhooks failed: {}", e),
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(poll_interval) => {}
+            _ = state.shutdown.wait() => return,
+        }
+    }
+}
+
+/// Send one batch of due deliveries, returns how many were attempted
+pub(crate) async fn deliver_due(state: &AppState, client: &Client) -> Result<usize, AppError> {
+    let config = &state.config.outgoing_webhooks;
+    // the lease outlives a request timing out, so a crashed worker's batch is retried later
+    let lease_secs = config.timeout_secs * 2 + 1;
+    let deliveries = PendingDelivery::claim(config.batch_size, lease_secs, &state.pool).await?;
+    let n = deliveries.len();
+    let results = join_all(deliveries.iter().map(|d| deliver(state, client, d))).await;
+    for ret in results {
+        ret?;
+    }
+    Ok(n)
+}
+
+async fn deliver(
+    state: &AppState,
+    client: &Client,
+    delivery: &PendingDelivery,
+) -> Result<(), AppError> {
+    let config = &state.config.outgoing_webhooks;
+    let pool = &state.pool;
+    // the host may resolve differently than when the webhook was created
+    if let Err(e) = check_url(&delivery.url, &state.config.outbound).await {
+        delivery
+            .record_failure(None, &e.to_string(), config, pool)
+            .await?;
+        warn!("delivery {} blocked: {}", delivery.id, e);
+        return Ok(());
+    }
+    let body = delivery.payload.to_string();
+    let timestamp = Utc::now().timestamp();
+    let ret = client
+        .post(&delivery.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(WEBHOOK_ID_HEADER, delivery.id)
+        .header(WEBHOOK_EVENT_HEADER, &delivery.event)
+        .header(WEBHOOK_TIMESTAMP_HEADER, timestamp)
+        .header(
+            WEBHOOK_SIGNATURE_HEADER,
+            sign_payload(&delivery.secret, timestamp, body.as_bytes()),
+        )
+        .body(body)
+        .send()
+        .await;
+
+    let (response_status, error) = match ret {
+        Ok(res) if res.status().is_success() => {
+            return delivery.record_success(res.status().as_u16(), pool).await;
+        }
+        Ok(res) => (Some(res.status().as_u16()), res.status().to_string()),
+        Err(e) => (None, e.to_string()),
+    };
+    let status = delivery
+        .record_failure(response_status, &error, config, pool)
+        .await?;
+    if status == DeliveryStatus::Dead {
+        warn!("delivery {} dead-lettered: {}", delivery.id, error);
+    } else {
+        info!("delivery {} failed, will retry: {}", delivery.id, error);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::OutboundConfig,
+        models::{CreateOutgoingWebhook, CreateUser, ListDeliveries, OutgoingWebhook},
+        AppConfig, User,
+    };
+    use anyhow::Result;
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, Bytes)>>>;
+
+    // a local receiver that fails the first request of every delivery and accepts retries
+    async fn receiver(
+        State(received): State<Received>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> StatusCode {
+        let mut received = received.lock().unwrap();
+        received.push((headers, body));
+        if received.len() == 1 {
+            StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            StatusCode::OK
+        }
+    }
+
+    async fn serve_receiver() -> Result<(String, Received)> {
+        let received = Received::default();
+        let app = Router::new()
+            .route("/hook", post(receiver))
+            .with_state(received.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/hook", listener.local_addr()?);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok((url, received))
+    }
+
+    #[tokio::test]
+    async fn deliveries_should_be_signed_and_retried() -> Result<()> {
+        let mut config = AppConfig::load()?;
+        config.outgoing_webhooks.base_delay_secs = 0;
+        config.outbound.allowed_hosts = vec!["127.0.0.1".to_string()];
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let (url, received) = serve_receiver().await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        sqlx::query("INSERT INTO chats (name, type, members) VALUES ('ci', 'group', $1)")
+            .bind(vec![user.id])
+            .execute(&state.pool)
+            .await?;
+        let input = CreateOutgoingWebhook {
+            url,
+            chat_id: None,
+            events: vec!["message.created".to_string()],
+        };
+        let webhook =
+            OutgoingWebhook::create(user.id, &input, &state.config.outbound, &state.pool).await?;
+        let data = serde_json::json!({ "content": "hello" });
+        OutgoingWebhook::enqueue(
+            "message.created",
+            1,
+            &data,
+            &mut *state.pool.acquire().await?,
+        )
+        .await?;
+
+        let client = Client::new();
+        assert_eq!(deliver_due(&state, &client).await?, 1);
+        assert_eq!(deliver_due(&state, &client).await?, 1);
+        assert_eq!(deliver_due(&state, &client).await?, 0);
+
+        let deliveries = OutgoingWebhook::deliveries(
+            user.id,
+            webhook.id,
+            &ListDeliveries::default(),
+            &state.pool,
+        )
+        .await?;
+        assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+        assert_eq!(deliveries[0].attempts, 2);
+        assert_eq!(deliveries[0].response_status, Some(200));
+
+        let received = received.lock().unwrap();
+        let (headers, body) = &received[1];
+        let timestamp: i64 = headers[WEBHOOK_TIMESTAMP_HEADER].to_str()?.parse()?;
+        let signature = sign_payload(&webhook.secret, timestamp, body);
+        assert_eq!(headers[WEBHOOK_SIGNATURE_HEADER], signature.as_str());
+        assert_eq!(headers[WEBHOOK_EVENT_HEADER], "message.created");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_deliveries_should_be_dead_lettered() -> Result<()> {
+        let mut config = AppConfig::load()?;
+        config.outgoing_webhooks.max_attempts = 1;
+        config.outbound.allowed_hosts = vec!["127.0.0.1".to_string()];
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        sqlx::query("INSERT INTO chats (name, type, members) VALUES ('ci', 'group', $1)")
+            .bind(vec![user.id])
+            .execute(&state.pool)
+            .await?;
+        // nothing listens on port 9 on localhost
+        let input = CreateOutgoingWebhook {
+            url: "http://127.0.0.1:9/hook".to_string(),
+            chat_id: Some(1),
+            events: vec!["message.created".to_string()],
+        };
+        let webhook =
+            OutgoingWebhook::create(user.id, &input, &state.config.outbound, &state.pool).await?;
+        OutgoingWebhook::enqueue(
+            "message.created",
+            1,
+            &"hello",
+            &mut *state.pool.acquire().await?,
+        )
+        .await?;
+
+        assert_eq!(deliver_due(&state, &Client::new()).await?, 1);
+        let input = ListDeliveries {
+            status: Some(DeliveryStatus::Dead),
+            ..Default::default()
+        };
+        let deliveries =
+            OutgoingWebhook::deliveries(user.id, webhook.id, &input, &state.pool).await?;
+        assert_eq!(deliveries.len(), 1);
+        assert!(deliveries[0].last_error.is_some());
+
+        OutgoingWebhook::retry_delivery(user.id, webhook.id, deliveries[0].id, &state.pool).await?;
+        assert_eq!(deliver_due(&state, &Client::new()).await?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deliveries_to_internal_hosts_should_be_blocked() -> Result<()> {
+        let mut config = AppConfig::load()?;
+        config.outgoing_webhooks.max_attempts = 1;
+        let (_tdb, state) = AppState::new_for_test(config).await?;
+        let (url, received) = serve_receiver().await?;
+        let input = CreateUser::new("Tyr Chen", "tchen@acme.org", "Hunter42");
+        let user = User::create(&input, &state.pool).await?;
+        sqlx::query("INSERT INTO chats (name, type, members) VALUES ('ci', 'group', $1)")
+            .bind(vec![user.id])
+            .execute(&state.pool)
+            .await?;
+        // allowed when created, the server config doesn't allow it any more
+        let allowed = OutboundConfig {
+            allowed_hosts: vec!["127.0.0.1".to_string()],
+        };
+        let input = CreateOutgoingWebhook {
+            url,
+            chat_id: None,
+            events: vec!["message.created".to_string()],
+        };
+        let webhook = OutgoingWebhook::create(user.id, &input, &allowed, &state.pool).await?;
+        OutgoingWebhook::enqueue(
+            "message.created",
+            1,
+            &"hello",
+            &mut *state.pool.acquire().await?,
+        )
+        .await?;
+
+        assert_eq!(deliver_due(&state, &Client::new()).await?, 1);
+        assert!(received.lock().unwrap().is_empty());
+        let deliveries = OutgoingWebhook::deliveries(
+            user.id,
+            webhook.id,
+            &ListDeliveries::default(),
+            &state.pool,
+        )
+        .await?;
+        assert_eq!(deliveries[0].status, DeliveryStatus::Dead);
+        assert!(deliveries[0]
+            .last_error
+            .as_ref()
+            .is_some_and(|e| e.contains("not a public address")));
+        Ok(())
+    }
+}
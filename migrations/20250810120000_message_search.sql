@@ -0,0 +1,8 @@
+-- full-text search for messages
+-- use the `simple` config since content mixes languages and we don't want stemming surprises
+ALTER TABLE messages
+    ADD COLUMN IF NOT EXISTS content_tsv tsvector
+        GENERATED ALWAYS AS (to_tsvector('simple', content)) STORED;
+
+-- create gin index for messages for content_tsv
+CREATE INDEX IF NOT EXISTS content_tsv_index ON messages USING GIN(content_tsv);
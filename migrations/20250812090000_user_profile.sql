@@ -0,0 +1,9 @@
+-- user profile fields
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS avatar_url varchar(256),
+    ADD COLUMN IF NOT EXISTS status_text varchar(128),
+    ADD COLUMN IF NOT EXISTS timezone varchar(64);
+
+-- create index for users for case-insensitive prefix search on fullname and email
+CREATE INDEX IF NOT EXISTS fullname_prefix_index ON users(lower(fullname) text_pattern_ops);
+CREATE INDEX IF NOT EXISTS email_prefix_index ON users(lower(email) text_pattern_ops);
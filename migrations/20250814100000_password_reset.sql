@@ -0,0 +1,13 @@
+-- single-use password reset tokens
+CREATE TABLE IF NOT EXISTS password_resets(
+    id bigserial PRIMARY KEY,
+    user_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- sha256 of the token sent by mail, hex encoded
+    token_hash char(64) NOT NULL UNIQUE,
+    expires_at timestamptz NOT NULL,
+    used_at timestamptz,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+
+-- create index for password_resets for user_id
+CREATE INDEX IF NOT EXISTS password_resets_user_id_index ON password_resets(user_id);
@@ -0,0 +1,16 @@
+-- email verification
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified_at timestamptz;
+
+-- single-use email verification tokens
+CREATE TABLE IF NOT EXISTS email_verifications(
+    id bigserial PRIMARY KEY,
+    user_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- sha256 of the token sent by mail, hex encoded
+    token_hash char(64) NOT NULL UNIQUE,
+    expires_at timestamptz NOT NULL,
+    used_at timestamptz,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+
+-- create index for email_verifications for user_id
+CREATE INDEX IF NOT EXISTS email_verifications_user_id_index ON email_verifications(user_id);
@@ -0,0 +1,20 @@
+-- totp two-factor authentication
+ALTER TABLE users
+    -- base32 encoded secret, set on enrollment and kept once confirmed
+    ADD COLUMN IF NOT EXISTS totp_secret varchar(64),
+    ADD COLUMN IF NOT EXISTS totp_enabled_at timestamptz,
+    -- last accepted time step, codes can't be replayed within their window
+    ADD COLUMN IF NOT EXISTS totp_last_step bigint;
+
+-- single-use recovery codes for when the authenticator is lost
+CREATE TABLE IF NOT EXISTS recovery_codes(
+    id bigserial PRIMARY KEY,
+    user_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- sha256 of the normalized code, hex encoded
+    code_hash char(64) NOT NULL,
+    used_at timestamptz,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+
+-- create index for recovery_codes for user_id
+CREATE INDEX IF NOT EXISTS recovery_codes_user_id_index ON recovery_codes(user_id);
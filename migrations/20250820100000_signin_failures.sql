@@ -0,0 +1,8 @@
+-- failed signin attempts, keyed by email and by client ip
+CREATE TABLE IF NOT EXISTS signin_failures(
+    -- email:<address> or ip:<address>
+    key varchar(128) PRIMARY KEY,
+    failures int NOT NULL DEFAULT 0,
+    locked_until timestamptz,
+    last_failed_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
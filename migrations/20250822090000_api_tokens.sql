@@ -0,0 +1,25 @@
+-- bot accounts, owned by the user who created them
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS is_bot boolean NOT NULL DEFAULT false,
+    ADD COLUMN IF NOT EXISTS bot_owner_id bigint REFERENCES users(id) ON DELETE CASCADE;
+
+-- create index for users for bot_owner_id
+CREATE INDEX IF NOT EXISTS bot_owner_id_index ON users(bot_owner_id);
+
+-- long-lived personal access tokens for scripts and bots
+CREATE TABLE IF NOT EXISTS api_tokens(
+    id bigserial PRIMARY KEY,
+    -- the identity the token acts as, a human user or one of their bots
+    user_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name varchar(64) NOT NULL,
+    -- sha256 of the token, hex encoded
+    token_hash char(64) NOT NULL UNIQUE,
+    scopes text[] NOT NULL,
+    last_used_at timestamptz,
+    expires_at timestamptz,
+    revoked_at timestamptz,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+
+-- create index for api_tokens for user_id
+CREATE INDEX IF NOT EXISTS api_tokens_user_id_index ON api_tokens(user_id);
@@ -0,0 +1,19 @@
+-- display name chosen by an integration, shown instead of the sender's fullname
+ALTER TABLE messages
+    ADD COLUMN IF NOT EXISTS sender_name varchar(64);
+
+-- incoming webhooks post into a chat as a bot, authenticated by the secret in the url
+CREATE TABLE IF NOT EXISTS incoming_webhooks(
+    id bigserial PRIMARY KEY,
+    chat_id bigint NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    -- the bot user messages are sent as
+    bot_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    creator_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name varchar(64) NOT NULL,
+    -- sha256 of the secret, hex encoded
+    token_hash char(64) NOT NULL UNIQUE,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+
+-- create index for incoming_webhooks for chat_id
+CREATE INDEX IF NOT EXISTS incoming_webhooks_chat_id_index ON incoming_webhooks(chat_id);
@@ -0,0 +1,48 @@
+-- outgoing webhooks, chat events are POSTed to the url signed with the secret
+CREATE TABLE IF NOT EXISTS outgoing_webhooks(
+    id bigserial PRIMARY KEY,
+    owner_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- NULL subscribes to every chat the owner is a member of
+    chat_id bigint REFERENCES chats(id) ON DELETE CASCADE,
+    url text NOT NULL,
+    -- hmac key, kept in plain text since it's needed to sign every delivery
+    secret varchar(64) NOT NULL,
+    events text[] NOT NULL,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+
+-- create index for outgoing_webhooks for owner_id
+CREATE INDEX IF NOT EXISTS outgoing_webhooks_owner_id_index ON outgoing_webhooks(owner_id);
+
+-- create index for outgoing_webhooks for chat_id
+CREATE INDEX IF NOT EXISTS outgoing_webhooks_chat_id_index ON outgoing_webhooks(chat_id);
+
+-- create delivery status: pending, delivered, dead
+CREATE TYPE delivery_status AS ENUM(
+    'pending',
+    'delivered',
+    'dead'
+    );
+
+-- durable delivery queue, also serves as the delivery log
+CREATE TABLE IF NOT EXISTS webhook_deliveries(
+    id bigserial PRIMARY KEY,
+    webhook_id bigint NOT NULL REFERENCES outgoing_webhooks(id) ON DELETE CASCADE,
+    event varchar(64) NOT NULL,
+    payload jsonb NOT NULL,
+    status delivery_status NOT NULL DEFAULT 'pending',
+    attempts integer NOT NULL DEFAULT 0,
+    -- also used as a lease while a worker is sending
+    next_attempt_at timestamptz NOT NULL DEFAULT now(),
+    response_status integer,
+    last_error text,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP,
+    delivered_at timestamptz
+);
+
+-- create index for webhook_deliveries for the pending queue
+CREATE INDEX IF NOT EXISTS webhook_deliveries_pending_index ON webhook_deliveries(next_attempt_at)
+    WHERE status = 'pending';
+
+-- create index for webhook_deliveries for webhook_id
+CREATE INDEX IF NOT EXISTS webhook_deliveries_webhook_id_index ON webhook_deliveries(webhook_id, id DESC);
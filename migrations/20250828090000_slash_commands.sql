@@ -0,0 +1,36 @@
+-- create message kind: text, action (/me), system (topic changes, joins...)
+CREATE TYPE message_kind AS ENUM(
+    'text',
+    'action',
+    'system'
+    );
+
+ALTER TABLE messages
+    ADD COLUMN IF NOT EXISTS kind message_kind NOT NULL DEFAULT 'text';
+
+ALTER TABLE chats
+    ADD COLUMN IF NOT EXISTS topic varchar(250);
+
+-- chats a user muted notifications for
+CREATE TABLE IF NOT EXISTS chat_mutes(
+    chat_id bigint NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    user_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chat_id, user_id)
+);
+
+-- external slash commands of a chat, dispatched to the url and answered by a bot
+CREATE TABLE IF NOT EXISTS slash_commands(
+    id bigserial PRIMARY KEY,
+    chat_id bigint NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    -- the bot public responses are posted as
+    bot_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    creator_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name varchar(32) NOT NULL,
+    url text NOT NULL,
+    -- hmac key used to sign requests to the url
+    secret varchar(64) NOT NULL,
+    description varchar(250),
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (chat_id, name)
+);
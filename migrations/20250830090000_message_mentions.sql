@@ -0,0 +1,17 @@
+-- how a user got mentioned: by name, @channel (all members) or @here (members online)
+CREATE TYPE mention_kind AS ENUM(
+    'user',
+    'channel',
+    'here'
+    );
+
+CREATE TABLE IF NOT EXISTS message_mentions(
+    message_id bigint NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind mention_kind NOT NULL,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (message_id, user_id)
+);
+
+-- "messages that mention me", newest first
+CREATE INDEX IF NOT EXISTS message_mentions_user_id_idx ON message_mentions(user_id, message_id DESC);
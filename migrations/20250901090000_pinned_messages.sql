@@ -0,0 +1,12 @@
+ALTER TABLE chats
+    ADD COLUMN IF NOT EXISTS description varchar(1000);
+
+-- messages pinned to the top of a chat
+CREATE TABLE IF NOT EXISTS pinned_messages(
+    message_id bigint PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    chat_id bigint NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    pinned_by bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS pinned_messages_chat_id_idx ON pinned_messages(chat_id, created_at DESC);
@@ -0,0 +1,11 @@
+-- messages a user saved for later, with an optional note
+CREATE TABLE IF NOT EXISTS bookmarks(
+    id bigserial PRIMARY KEY,
+    user_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    message_id bigint NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    note varchar(500),
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (user_id, message_id)
+);
+
+CREATE INDEX IF NOT EXISTS bookmarks_user_id_idx ON bookmarks(user_id, id DESC);
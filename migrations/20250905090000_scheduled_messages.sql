@@ -0,0 +1,25 @@
+CREATE TYPE scheduled_status AS ENUM(
+    'pending',
+    'sent',
+    'cancelled',
+    -- the sender couldn't post anymore when it was due, e.g. they left the chat
+    'failed'
+    );
+
+-- messages composed now and sent later by the scheduled message worker
+CREATE TABLE IF NOT EXISTS scheduled_messages(
+    id bigserial PRIMARY KEY,
+    chat_id bigint NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    sender_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    content text NOT NULL,
+    images text[] NOT NULL DEFAULT '{}',
+    send_at timestamptz NOT NULL,
+    status scheduled_status NOT NULL DEFAULT 'pending',
+    -- the message it was sent as
+    message_id bigint REFERENCES messages(id) ON DELETE SET NULL,
+    error text,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS scheduled_messages_due_idx ON scheduled_messages(send_at) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS scheduled_messages_sender_id_idx ON scheduled_messages(sender_id, send_at);
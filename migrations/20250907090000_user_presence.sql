@@ -0,0 +1,5 @@
+-- users with an open event stream on notify_server, refreshed while they stay connected
+CREATE TABLE IF NOT EXISTS user_presence(
+    user_id bigint PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    last_seen_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,5 @@
+-- a send that fails for a transient reason is retried later instead of blocking the queue,
+-- and marked failed after too many attempts
+ALTER TABLE scheduled_messages
+    ADD COLUMN IF NOT EXISTS attempts integer NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS next_attempt_at timestamptz;
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use chat_core::{ConfigSource, LogConfig, TracingConfig};
+use serde::{Deserialize, Serialize};
+
+/// fields are overridden by env vars like NOTIFY_SERVER__PORT or NOTIFY_LOG__LEVEL
+const CONFIG_SOURCE: ConfigSource = ConfigSource {
+    path_env: "NOTIFY_CONFIG",
+    default_paths: &["notify.yaml", "/etc/config/notify.yaml"],
+    override_prefix: "NOTIFY_",
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub log: LogConfig,
+    #[serde(default)]
+    pub tracing: TracingConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthConfig {
+    pub pk: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub port: u16,
+    pub db_url: String,
+    /// port of /metrics, kept apart from the public one, metrics aren't served when not set
+    #[serde(default)]
+    pub metrics_port: Option<u16>,
+    /// how long open requests get to finish on shutdown, event streams are ended right away
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+}
+
+impl AppConfig {
+    /// Load the config without a path given on the command line
+    pub fn load() -> Result<Self> {
+        Self::load_from(None)
+    }
+
+    /// Read the first config found of: `path` (the --config flag), $NOTIFY_CONFIG, ./notify.yaml
+    /// and /etc/config/notify.yaml, apply NOTIFY_<FIELD>__<FIELD> env overrides and validate the
+    /// result
+    pub fn load_from(path: Option<PathBuf>) -> Result<Self> {
+        let (config, path): (Self, _) = CONFIG_SOURCE.load(path)?;
+
+        let mut errors = Vec::new();
+        config.log.validate(&mut errors);
+        config.tracing.validate(&mut errors);
+        if !errors.is_empty() {
+            bail!(
+                "invalid config {}:\n  {}",
+                path.display(),
+                errors.join("\n  ")
+            );
+        }
+        Ok(config)
+    }
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
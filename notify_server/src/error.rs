@@ -0,0 +1,50 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::error;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorOutput {
+    /// stable, machine-readable, e.g. "unauthorized"
+    pub code: String,
+    pub error: String,
+}
+
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("sql error: {0}")]
+    SqlxError(#[from] sqlx::Error),
+
+    #[error("jwt error: {0}")]
+    JwtError(#[from] jwt_simple::Error),
+
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, code) = match &self {
+            Self::Unauthorized(_) => (StatusCode::UNAUTHORIZED, "unauthorized"),
+            Self::SqlxError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+            Self::JwtError(_) => (StatusCode::FORBIDDEN, "invalid_token"),
+            Self::IoError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+        };
+        // keep db details in the logs
+        let error = if status.is_server_error() {
+            error!("internal error: {}", self);
+            "internal server error".to_string()
+        } else {
+            self.to_string()
+        };
+        let output = ErrorOutput {
+            code: code.to_string(),
+            error,
+        };
+        (status, Json(output)).into_response()
+    }
+}
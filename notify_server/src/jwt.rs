@@ -0,0 +1,51 @@
+use crate::{AppError, User};
+use jwt_simple::prelude::*;
+
+// must match the claims chat_server signs with
+const JWT_ISS: &str = "chat_server";
+const JWT_AUD: &str = "chat_web";
+
+pub struct DecodingKey(Ed25519PublicKey);
+
+impl DecodingKey {
+    pub fn load(pem: &str) -> Result<Self, AppError> {
+        Ok(Self(Ed25519PublicKey::from_pem(pem)?))
+    }
+
+    pub fn verify(&self, token: &str) -> Result<User, AppError> {
+        let opts = VerificationOptions {
+            allowed_issuers: Some(HashSet::from_strings(&[JWT_ISS])),
+            allowed_audiences: Some(HashSet::from_strings(&[JWT_AUD])),
+            ..Default::default()
+        };
+
+        let claims = self.0.verify_token::<User>(token, Some(opts))?;
+        Ok(claims.custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    // the pending token of a two-factor signin, see chat_server's EncodingKey::sign_mfa
+    #[test]
+    fn only_access_tokens_should_be_accepted() -> Result<()> {
+        let sk = Ed25519KeyPair::from_pem(include_str!("../../chat_server/fixtures/encoding.pem"))?;
+        let dk = DecodingKey::load(include_str!("../../chat_server/fixtures/decoding.pem"))?;
+        let user = User {
+            id: 1,
+            fullname: "Tyr Chen".to_string(),
+            email: "tchen@acme.org".to_string(),
+        };
+        let sign = |aud: &str| {
+            let claims = Claims::with_custom_claims(user.clone(), Duration::from_secs(60));
+            sk.sign(claims.with_issuer(JWT_ISS).with_audience(aud))
+        };
+
+        assert_eq!(dk.verify(&sign(JWT_AUD)?)?.id, 1);
+        assert!(dk.verify(&sign("chat_mfa")?).is_err());
+        Ok(())
+    }
+}
@@ -1,20 +1,167 @@
+mod config;
+mod error;
+mod health;
+mod jwt;
+mod metrics;
+mod middlewares;
+mod notif;
+mod presence;
+mod shutdown;
 mod sse;
+mod telemetry;
 
+use crate::metrics::metrics_router;
 use axum::{
+    middleware::{from_fn, from_fn_with_state},
     response::{Html, IntoResponse},
     routing::get,
     Router,
 };
+use chat_core::{prometheus, track_metrics};
+use dashmap::DashMap;
+use health::{health_handler, ready_handler};
+use jwt::DecodingKey;
+use middlewares::verify_token;
+use notif::{disconnect_all, setup_pg_listener, AppEvent};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgPoolOptions, PgPool};
 use sse::sse_handler;
+use std::{
+    future::{Future, IntoFuture},
+    ops::Deref,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, watch},
+};
+use tracing::{info, warn};
+
+pub use chat_core::{config_arg, init_logging, shutdown_tracing};
+pub use config::AppConfig;
+pub use error::{AppError, ErrorOutput};
+pub use shutdown::shutdown_signal;
 
 const INDEX_HTML: &str = include_str!("../index.html");
 
-pub fn get_router() -> Router {
+/// The claims of a chat_server token, other fields are ignored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub id: i64,
+    pub fullname: String,
+    pub email: String,
+}
+
+#[derive(Clone)]
+pub struct AppState(Arc<AppStateInner>);
+
+pub struct AppStateInner {
+    pub config: AppConfig,
+    dk: DecodingKey,
+    /// connected users and the channel feeding their event streams
+    users: DashMap<i64, broadcast::Sender<Arc<AppEvent>>>,
+    /// used by the readiness check and for presence, events come through the listener
+    pool: PgPool,
+    /// the notification listener is connected
+    listening: AtomicBool,
+    shutting_down: AtomicBool,
+}
+
+pub async fn get_router(config: AppConfig) -> Result<Router, AppError> {
+    let state = AppState::try_new(config)?;
+    setup_pg_listener(state.clone()).await?;
+    Ok(router(state))
+}
+
+/// Serve until `signal` resolves, then tell the connected clients to reconnect, end their
+/// streams and give other requests `server.shutdown_timeout_secs` to finish
+pub async fn serve(
+    listener: TcpListener,
+    config: AppConfig,
+    signal: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), AppError> {
+    let state = AppState::try_new(config)?;
+    setup_pg_listener(state.clone()).await?;
+    tokio::spawn(presence::heartbeat(state.clone()));
+    if let Some(port) = state.config.server.metrics_port {
+        let addr = format!("0.0.0.0:{}", port);
+        let metrics = TcpListener::bind(&addr).await?;
+        info!("serving metrics on: {}", addr);
+        tokio::spawn(async move { axum::serve(metrics, metrics_router()).await });
+    }
+
+    let timeout = Duration::from_secs(state.config.server.shutdown_timeout_secs);
+    let (tx, mut rx) = watch::channel(false);
+    let graceful = {
+        let state = state.clone();
+        async move {
+            signal.await;
+            info!("shutting down, {} users connected", state.users.len());
+            state.shutting_down.store(true, Ordering::Relaxed);
+            disconnect_all(&state);
+            tx.send_replace(true);
+        }
+    };
+    let server =
+        axum::serve(listener, router(state).into_make_service()).with_graceful_shutdown(graceful);
+    let deadline = async {
+        let _ = rx.wait_for(|triggered| *triggered).await;
+        tokio::time::sleep(timeout).await;
+    };
+
+    tokio::select! {
+        ret = server.into_future() => ret?,
+        _ = deadline => warn!(
+            "requests still running after {}s, closing them",
+            timeout.as_secs()
+        ),
+    }
+    info!("shutdown complete");
+    Ok(())
+}
+
+fn router(state: AppState) -> Router {
     Router::new()
-        .route("/", get(index_handler))
         .route("/events", get(sse_handler))
+        .layer(from_fn_with_state(state.clone(), verify_token))
+        .route("/", get(index_handler))
+        .route("/healthz", get(health_handler))
+        .route("/readyz", get(ready_handler))
+        .layer(from_fn(track_metrics))
+        .with_state(state)
 }
 
 async fn index_handler() -> impl IntoResponse {
     Html(INDEX_HTML)
 }
+
+impl Deref for AppState {
+    type Target = AppStateInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AppState {
+    pub fn try_new(config: AppConfig) -> Result<Self, AppError> {
+        // install the recorder before anything records
+        prometheus();
+        let dk = DecodingKey::load(&config.auth.pk)?;
+        let pool = PgPoolOptions::new()
+            .max_connections(2)
+            .connect_lazy(&config.server.db_url)?;
+        Ok(Self(Arc::new(AppStateInner {
+            config,
+            dk,
+            users: DashMap::new(),
+            pool,
+            listening: AtomicBool::new(false),
+            shutting_down: AtomicBool::new(false),
+        })))
+    }
+}
@@ -1,21 +1,22 @@
 use anyhow::Result;
-use notify_server::get_router;
+use notify_server::{
+    config_arg, init_logging, serve, shutdown_signal, shutdown_tracing, AppConfig,
+};
 use tokio::net::TcpListener;
-use tracing::{info, level_filters::LevelFilter};
-use tracing_subscriber::{fmt::Layer, layer::SubscriberExt, util::SubscriberInitExt, Layer as _};
+use tracing::info;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let layer = Layer::new().with_filter(LevelFilter::INFO);
-    tracing_subscriber::registry().with(layer).init();
+    let config = AppConfig::load_from(config_arg())?;
+    let _log_guard = init_logging("notify_server", &config.log, &config.tracing)?;
+    let addr = format!("0.0.0.0:{}", config.server.port);
 
-    let addr = "0.0.0.0:6687";
-
-    let app = get_router();
     let listener = TcpListener::bind(&addr).await?;
     info!("Listening on: {}", addr);
 
-    axum::serve(listener, app.into_make_service()).await?;
+    let ret = serve(listener, config, shutdown_signal()).await;
+    shutdown_tracing();
+    ret?;
 
     Ok(())
 }
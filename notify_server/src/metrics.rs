@@ -0,0 +1,27 @@
+use axum::{routing::get, Router};
+use chat_core::render_metrics;
+use metrics::gauge;
+
+pub(crate) const FANOUT_LAG: &str = "event_fanout_lag_seconds";
+const SSE_CONNECTIONS: &str = "sse_connections";
+
+/// /metrics, served on `server.metrics_port` only, so it isn't reachable where clients connect
+pub(crate) fn metrics_router() -> Router {
+    Router::new().route("/metrics", get(|| async { render_metrics() }))
+}
+
+/// Counts an open event stream for as long as it is alive
+pub(crate) struct SseConnection;
+
+impl SseConnection {
+    pub(crate) fn open() -> Self {
+        gauge!(SSE_CONNECTIONS).increment(1.0);
+        Self
+    }
+}
+
+impl Drop for SseConnection {
+    fn drop(&mut self) {
+        gauge!(SSE_CONNECTIONS).decrement(1.0);
+    }
+}
@@ -0,0 +1,46 @@
+use axum::{
+    extract::{FromRequestParts, Query, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::{
+    headers::{authorization::Bearer, Authorization},
+    TypedHeader,
+};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{AppError, AppState};
+
+#[derive(Debug, Deserialize)]
+struct TokenParams {
+    access_token: String,
+}
+
+/// Accept the token as a bearer header, or as `?access_token=` since EventSource can't set headers
+pub async fn verify_token(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let (mut parts, body) = req.into_parts();
+    let token = match TypedHeader::<Authorization<Bearer>>::from_request_parts(&mut parts, &state)
+        .await
+    {
+        Ok(TypedHeader(Authorization(bearer))) => bearer.token().to_string(),
+        Err(_) => match Query::<TokenParams>::from_request_parts(&mut parts, &state).await {
+            Ok(Query(params)) => params.access_token,
+            Err(_) => {
+                return AppError::Unauthorized("missing access token".to_string()).into_response();
+            }
+        },
+    };
+
+    match state.dk.verify(&token) {
+        Ok(user) => {
+            let mut req = Request::from_parts(parts, body);
+            req.extensions_mut().insert(user);
+            next.run(req).await
+        }
+        Err(e) => {
+            warn!("{}", e);
+            e.into_response()
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{metrics::FANOUT_LAG, telemetry::parent_context, AppError, AppState};
+use metrics::{counter, histogram};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{info, info_span, warn};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// chat_server notifies on this channel when members get mentioned
+const MENTION_CHANNEL: &str = "chat_mention";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AppEvent {
+    Mention(MentionEvent),
+    /// the server is going away, clients should connect again
+    Reconnect,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MentionEvent {
+    /// mentions are "high", clients may alert even when the chat isn't focused
+    pub priority: String,
+    pub message_id: i64,
+    pub chat_id: i64,
+    pub sender_id: i64,
+    /// the start of the message content
+    pub excerpt: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Notification {
+    /// the users the event is for, chat_server already left out those who muted the chat
+    user_ids: Vec<i64>,
+    /// unix millis when chat_server sent it
+    #[serde(default)]
+    sent_at: Option<i64>,
+    /// the trace of the chat_server request that caused the event
+    #[serde(default)]
+    traceparent: Option<String>,
+    #[serde(flatten)]
+    event: AppEvent,
+}
+
+impl AppEvent {
+    /// The name of the server-sent event
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Mention(_) => "mention",
+            Self::Reconnect => "reconnect",
+        }
+    }
+}
+
+/// Listen for notifications from chat_server and forward them to the connected users
+pub async fn setup_pg_listener(state: AppState) -> Result<(), AppError> {
+    let mut listener = listen(&state.config.server.db_url).await?;
+    state.listening.store(true, Ordering::Relaxed);
+
+    tokio::spawn(async move {
+        loop {
+            let notification = match listener.try_recv().await {
+                Ok(Some(notification)) => notification,
+                // a new listener is only kept once it listens again, until then /readyz fails
+                lost => {
+                    if let Err(e) = lost {
+                        warn!("receive notification failed: {}", e);
+                    }
+                    state.listening.store(false, Ordering::Relaxed);
+                    listener = relisten(&state.config.server.db_url).await;
+                    state.listening.store(true, Ordering::Relaxed);
+                    continue;
+                }
+            };
+            match serde_json::from_str::<Notification>(notification.payload()) {
+                Ok(notification) => dispatch(&state, notification),
+                Err(e) => warn!("invalid notification {}: {}", notification.payload(), e),
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn listen(db_url: &str) -> Result<PgListener, sqlx::Error> {
+    let mut listener = PgListener::connect(db_url).await?;
+    listener.listen(MENTION_CHANNEL).await?;
+    info!("listening on channel {}", MENTION_CHANNEL);
+    Ok(listener)
+}
+
+// notifications sent while disconnected are lost, postgres doesn't queue them
+async fn relisten(db_url: &str) -> PgListener {
+    loop {
+        match listen(db_url).await {
+            Ok(listener) => return listener,
+            Err(e) => {
+                warn!("listen again failed: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+fn dispatch(state: &AppState, notification: Notification) {
+    let span = info_span!(
+        "dispatch",
+        event = notification.event.name(),
+        users = notification.user_ids.len()
+    );
+    if let Some(traceparent) = &notification.traceparent {
+        span.set_parent(parent_context(traceparent));
+    }
+    let _enter = span.enter();
+
+    if let Some(sent_at) = notification.sent_at {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64);
+        histogram!(FANOUT_LAG).record((now - sent_at).max(0) as f64 / 1000.0);
+    }
+    let event = Arc::new(notification.event);
+    let name = event.name();
+    for user_id in notification.user_ids {
+        let Some(tx) = state.users.get(&user_id).map(|tx| tx.clone()) else {
+            continue;
+        };
+        counter!("events_dispatched_total", "event" => name).increment(1);
+        // nobody listens anymore, all streams of the user are closed
+        if tx.send(event.clone()).is_err() {
+            state
+                .users
+                .remove_if(&user_id, |_, tx| tx.receiver_count() == 0);
+        }
+    }
+}
+
+/// Send every connected user a reconnect event and end their streams, used on shutdown
+pub(crate) fn disconnect_all(state: &AppState) {
+    let event = Arc::new(AppEvent::Reconnect);
+    // dropping the senders ends the streams once they drained the event
+    state.users.retain(|_, tx| {
+        let _ = tx.send(event.clone());
+        false
+    });
+}
@@ -0,0 +1,46 @@
+use crate::AppState;
+use sqlx::PgPool;
+use std::{sync::atomic::Ordering, time::Duration};
+use tracing::warn;
+
+/// How often connected users are marked as seen, chat_server takes users not seen for three
+/// intervals as offline
+const PRESENCE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Record that the users have an open event stream, so `@here` reaches them
+pub(crate) async fn mark_online(user_ids: &[i64], pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_presence (user_id, last_seen_at)
+        SELECT id, now() FROM users WHERE id = ANY($1)
+        ON CONFLICT (user_id) DO UPDATE SET last_seen_at = now()
+        "#,
+    )
+    .bind(user_ids)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Refresh the presence of the connected users until shutdown
+pub(crate) async fn heartbeat(state: AppState) {
+    loop {
+        tokio::time::sleep(PRESENCE_INTERVAL).await;
+        if state.shutting_down.load(Ordering::Relaxed) {
+            return;
+        }
+        // closed streams are only removed on the next event, skip them
+        let user_ids: Vec<i64> = state
+            .users
+            .iter()
+            .filter(|entry| entry.receiver_count() > 0)
+            .map(|entry| *entry.key())
+            .collect();
+        if user_ids.is_empty() {
+            continue;
+        }
+        if let Err(e) = mark_online(&user_ids, &state.pool).await {
+            warn!("update presence failed: {}", e);
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use tokio::signal::{
+    self,
+    unix::{signal, SignalKind},
+};
+use tracing::warn;
+
+/// Resolves on ctrl-c or SIGTERM
+pub async fn shutdown_signal() {
+    let terminate = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                warn!("listen for SIGTERM failed: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    tokio::select! {
+        _ = signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+}
@@ -1,22 +1,62 @@
-use axum::response::{sse::Event, Sse};
-use axum_extra::{headers, TypedHeader};
-use futures::{stream, Stream};
+use crate::{metrics::SseConnection, presence::mark_online, AppEvent, AppState, User};
+use axum::{
+    extract::State,
+    response::{sse::Event, Sse},
+    Extension,
+};
+use futures::Stream;
+use metrics::counter;
 use std::{convert::Infallible, time::Duration};
-use tokio_stream::StreamExt;
-use tracing::info;
+use tokio::sync::broadcast;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    StreamExt,
+};
+use tracing::{info, warn};
+
+// events buffered per user, a client lagging further behind misses them
+const CHANNEL_CAPACITY: usize = 256;
+// give a restarting server time to come back before clients connect again
+const RECONNECT_DELAY: Duration = Duration::from_secs(3);
 
 pub(crate) async fn sse_handler(
-    TypedHeader(user_agent): TypedHeader<headers::UserAgent>,
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    info!("`{}` connected", user_agent.as_str());
+    info!("user {} connected", user.id);
+    {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = mark_online(&[user.id], &state.pool).await {
+                warn!("update presence of user {} failed: {}", user.id, e);
+            }
+        });
+    }
 
-    // A `Stream` that repeats an event every second
-    //
-    // You can also create streams from tokio channels using the wrappers in
-    // https://docs.rs/tokio-stream
-    let stream = stream::repeat_with(|| Event::default().data("hi!"))
-        .map(Ok)
-        .throttle(Duration::from_secs(1));
+    // a user may have several streams open, e.g. one per browser tab
+    let rx = state
+        .users
+        .entry(user.id)
+        .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+        .subscribe();
+    // lives in the stream, so the gauge goes down when the client disconnects
+    let connection = SseConnection::open();
+    let stream = BroadcastStream::new(rx).filter_map(move |event| {
+        let _connection = &connection;
+        let event = match event {
+            Ok(event) => event,
+            Err(BroadcastStreamRecvError::Lagged(n)) => {
+                counter!("events_dropped_total").increment(n);
+                return None;
+            }
+        };
+        let data = serde_json::to_string(&*event).expect("event should serialize");
+        let sse = Event::default().event(event.name()).data(data);
+        match *event {
+            AppEvent::Reconnect => Some(Ok(sse.retry(RECONNECT_DELAY))),
+            _ => Some(Ok(sse)),
+        }
+    });
 
     Sse::new(stream).keep_alive(
         axum::response::sse::KeepAlive::new()
@@ -0,0 +1,10 @@
+use opentelemetry::{global, Context};
+use std::collections::HashMap;
+
+const TRACEPARENT: &str = "traceparent";
+
+/// The trace a notification belongs to, from the `traceparent` chat_server put into it
+pub(crate) fn parent_context(traceparent: &str) -> Context {
+    let carrier = HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+    global::get_text_map_propagator(|propagator| propagator.extract(&carrier))
+}